@@ -5,20 +5,26 @@ use cait_sith::{
     protocol::{run_protocol, Participant, Protocol},
     sign,
     triples::{
-        self, generate_triple, setup, Setup, TripleGenerationOutput, TriplePub, TripleShare,
+        self, generate_triple, generate_triple_many, TripleGenerationOutput, TriplePub, TripleShare,
     },
     FullSignature, KeygenOutput, PresignArguments, PresignOutput,
 };
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use k256::{AffinePoint, Secp256k1, Scalar};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use k256::{AffinePoint, Scalar, Secp256k1};
 use rand_core::OsRng;
 
-fn run_setup(participants: Vec<Participant>) -> Vec<(Participant, Setup)> {
-    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Setup>>)> =
-        Vec::with_capacity(participants.len());
+fn run_triple_generation(
+    participants: Vec<Participant>,
+    threshold: usize,
+) -> Vec<(Participant, TripleGenerationOutput<Secp256k1>)> {
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = TripleGenerationOutput<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
 
     for p in participants.iter() {
-        let protocol = setup::<Secp256k1>(&participants, *p);
+        let protocol = generate_triple(&participants, *p, threshold, b"bench triple");
         assert!(protocol.is_ok());
         let protocol = protocol.unwrap();
         protocols.push((*p, Box::new(protocol)));
@@ -27,22 +33,23 @@ fn run_setup(participants: Vec<Participant>) -> Vec<(Participant, Setup)> {
     run_protocol(protocols).unwrap()
 }
 
-fn run_triple_generation(
-    participants: Vec<(Participant, Setup)>,
+fn run_triple_generation_many(
+    participants: Vec<Participant>,
     threshold: usize,
-) -> Vec<(Participant, TripleGenerationOutput<Secp256k1>)> {
+    count: usize,
+) -> Vec<(Participant, Vec<TripleGenerationOutput<Secp256k1>>)> {
+    #[allow(clippy::type_complexity)]
     let mut protocols: Vec<(
         Participant,
-        Box<dyn Protocol<Output = TripleGenerationOutput<Secp256k1>>>,
+        Box<dyn Protocol<Output = Vec<TripleGenerationOutput<Secp256k1>>>>,
     )> = Vec::with_capacity(participants.len());
 
-    let just_participants: Vec<_> = participants.iter().map(|(p, _)| *p).collect();
-
-    for (p, setup) in participants.into_iter() {
-        let protocol = generate_triple(&just_participants, p, setup, threshold);
+    for p in participants.iter() {
+        let protocol =
+            generate_triple_many(&participants, *p, threshold, count, b"bench triple many");
         assert!(protocol.is_ok());
         let protocol = protocol.unwrap();
-        protocols.push((p, Box::new(protocol)));
+        protocols.push((*p, Box::new(protocol)));
     }
 
     run_protocol(protocols).unwrap()
@@ -52,11 +59,14 @@ fn run_keygen(
     participants: Vec<Participant>,
     threshold: usize,
 ) -> Vec<(Participant, KeygenOutput<Secp256k1>)> {
-    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>)> =
-        Vec::with_capacity(participants.len());
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
 
     for p in participants.iter() {
-        let protocol = keygen(&participants, *p, threshold);
+        let protocol = keygen(&participants, *p, threshold, b"bench keygen");
         assert!(protocol.is_ok());
         let protocol = protocol.unwrap();
         protocols.push((*p, Box::new(protocol)));
@@ -76,26 +86,25 @@ fn run_presign(
     assert!(participants.len() == shares0.len());
     assert!(participants.len() == shares1.len());
 
-    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>)> =
-        Vec::with_capacity(participants.len());
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
 
     let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
 
-    for (((p, keygen_out), share0), share1) in participants
-        .into_iter()
-        .zip(shares0.into_iter())
-        .zip(shares1.into_iter())
-    {
+    for (((p, keygen_out), share0), share1) in participants.into_iter().zip(shares0).zip(shares1) {
         let protocol = presign(
             &participant_list,
             p,
             PresignArguments {
-                original_threshold: threshold,
                 triple0: (share0, pub0.clone()),
                 triple1: (share1, pub1.clone()),
                 keygen_out,
                 threshold,
             },
+            b"bench presign",
         );
         assert!(protocol.is_ok());
         let protocol = protocol.unwrap();
@@ -110,13 +119,23 @@ fn run_sign(
     public_key: AffinePoint,
     msg: Scalar,
 ) -> Vec<(Participant, FullSignature<Secp256k1>)> {
-    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = FullSignature<Secp256k1>>>)> =
-        Vec::with_capacity(participants.len());
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
 
     let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
 
     for (p, presign_out) in participants.into_iter() {
-        let protocol = sign(&participant_list, p, public_key, presign_out, msg);
+        let protocol = sign(
+            &participant_list,
+            p,
+            public_key,
+            presign_out,
+            msg,
+            b"bench sign",
+        );
         assert!(protocol.is_ok());
         let protocol = protocol.unwrap();
         protocols.push((p, Box::new(protocol)));
@@ -133,15 +152,20 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     ];
     let t = 3;
 
-    c.bench_function("setup 3", |b| b.iter(|| run_setup(participants.clone())));
-
-    let mut setup_result = run_setup(participants.clone());
-    setup_result.sort_by_key(|(p, _)| *p);
-
     c.bench_function("triple generation (3, 3)", |b| {
-        b.iter(|| run_triple_generation(black_box(setup_result.clone()), t))
+        b.iter(|| run_triple_generation(black_box(participants.clone()), t))
     });
 
+    let mut group = c.benchmark_group("triple generation many (3, 3)");
+    for count in [1, 4, 16] {
+        // Reporting throughput in elements shows the cost per triple.
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            b.iter(|| run_triple_generation_many(black_box(participants.clone()), t, count))
+        });
+    }
+    group.finish();
+
     c.bench_function("keygen (3,3)", |b| {
         b.iter(|| run_keygen(black_box(participants.clone()), black_box(t)))
     });
@@ -204,7 +204,13 @@ fn main() {
     );
     let start = Instant::now();
     let results = run_protocol(latency, bandwidth, &participants, |p| {
-        triples::generate_triple::<Secp256k1>(&participants, p, args.parties as usize).unwrap()
+        triples::generate_triple::<Secp256k1>(
+            &participants,
+            p,
+            args.parties as usize,
+            b"network-benches triple",
+        )
+        .unwrap()
     });
     let stop = Instant::now();
     println!("time:\t{:#?}", stop.duration_since(start));
@@ -218,7 +224,7 @@ fn main() {
     );
     let start = Instant::now();
     let results = run_protocol(latency, bandwidth, &participants, |p| {
-        keygen(&participants, p, args.parties as usize).unwrap()
+        keygen(&participants, p, args.parties as usize, b"network-benches keygen").unwrap()
     });
     let stop = Instant::now();
     println!("time:\t{:#?}", stop.duration_since(start));
@@ -249,6 +255,7 @@ fn main() {
                 keygen_out: shares[&p].clone(),
                 threshold: args.parties as usize,
             },
+            b"network-benches presign",
         )
         .unwrap()
     });
@@ -270,6 +277,7 @@ fn main() {
             shares[&p].public_key,
             presignatures[&p].clone(),
             scalar_hash(b"hello world"),
+            b"network-benches sign",
         )
         .unwrap()
     });
@@ -67,6 +67,125 @@ mod k256_impl {
     }
 }
 
+/// Reduce a wide integer, split into two big endian halves, modulo the order of the curve.
+///
+/// Reducing twice as many bytes as a scalar takes makes the bias of the result
+/// negligible, for curves which don't support reducing wide integers directly.
+#[cfg(any(feature = "p256", feature = "p384"))]
+fn reduce_wide<C: CSCurve>(
+    hi: &elliptic_curve::FieldBytes<C>,
+    lo: &elliptic_curve::FieldBytes<C>,
+) -> C::Scalar {
+    use elliptic_curve::Field;
+
+    // The value of 2^64, raised to the number of 64 bit limbs in each half.
+    let two_64 = C::Scalar::from(u64::MAX) + C::Scalar::ONE;
+    let shift = two_64.pow_vartime([u64::try_from(hi.len() / 8).unwrap()]);
+    <C::Scalar as Reduce<C::Uint>>::reduce_bytes(hi) * shift
+        + <C::Scalar as Reduce<C::Uint>>::reduce_bytes(lo)
+}
+
+#[cfg(feature = "p256")]
+mod p256_impl {
+    use super::*;
+
+    use elliptic_curve::{bigint::Bounded, FieldBytes};
+    use p256::NistP256;
+
+    impl CSCurve for NistP256 {
+        const NAME: &'static [u8] = b"P-256";
+        const BITS: usize = <Self::Uint as Bounded>::BITS;
+
+        fn serialize_point<S: Serializer>(
+            point: &Self::AffinePoint,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            point.serialize(serializer)
+        }
+
+        fn deserialize_point<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Self::AffinePoint, D::Error> {
+            Self::AffinePoint::deserialize(deserializer)
+        }
+
+        fn sample_scalar_constant_time<R: CryptoRngCore>(r: &mut R) -> Self::Scalar {
+            let mut hi = FieldBytes::<Self>::default();
+            let mut lo = FieldBytes::<Self>::default();
+            r.fill_bytes(&mut hi);
+            r.fill_bytes(&mut lo);
+            reduce_wide::<Self>(&hi, &lo)
+        }
+    }
+}
+
+#[cfg(feature = "p384")]
+mod p384_impl {
+    use super::*;
+
+    use elliptic_curve::{
+        bigint::Bounded,
+        sec1::{FromEncodedPoint, ToEncodedPoint},
+        FieldBytes,
+    };
+    use p384::{EncodedPoint, NistP384};
+
+    // Points don't implement serde here, so we use their compressed encoding directly,
+    // which is what the other curves do as well.
+    impl CSCurve for NistP384 {
+        const NAME: &'static [u8] = b"P-384";
+        const BITS: usize = <Self::Uint as Bounded>::BITS;
+
+        fn serialize_point<S: Serializer>(
+            point: &Self::AffinePoint,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            point.to_encoded_point(true).serialize(serializer)
+        }
+
+        fn deserialize_point<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Self::AffinePoint, D::Error> {
+            let encoded = EncodedPoint::deserialize(deserializer)?;
+            Option::from(Self::AffinePoint::from_encoded_point(&encoded))
+                .ok_or_else(|| serde::de::Error::custom("invalid point"))
+        }
+
+        fn sample_scalar_constant_time<R: CryptoRngCore>(r: &mut R) -> Self::Scalar {
+            let mut hi = FieldBytes::<Self>::default();
+            let mut lo = FieldBytes::<Self>::default();
+            r.fill_bytes(&mut hi);
+            r.fill_bytes(&mut lo);
+            reduce_wide::<Self>(&hi, &lo)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "p256"))]
+mod test_reduce_wide {
+    use super::*;
+
+    use elliptic_curve::FieldBytes;
+    use p256::{NistP256, Scalar};
+
+    #[test]
+    fn test_reduce_wide() {
+        let zero = FieldBytes::<NistP256>::default();
+        let mut one = zero;
+        one[31] = 1;
+        let max = FieldBytes::<NistP256>::from([0xFF; 32]);
+
+        assert_eq!(reduce_wide::<NistP256>(&zero, &one), Scalar::ONE);
+        // 2^256 is one more than the largest value of a single half.
+        let two_256 = reduce_wide::<NistP256>(&one, &zero);
+        assert_eq!(
+            two_256,
+            <Scalar as Reduce<<NistP256 as Curve>::Uint>>::reduce_bytes(&max) + Scalar::ONE
+        );
+        assert_eq!(reduce_wide::<NistP256>(&one, &one), two_256 + Scalar::ONE);
+    }
+}
+
 #[cfg(test)]
 mod test_scalar_hash {
     use super::*;
@@ -0,0 +1,250 @@
+//! Non-hardened key derivation, following BIP32.
+//!
+//! With non-hardened derivation, each child key is the parent key plus a tweak,
+//! and this tweak can be computed from public information alone: the parent
+//! public key, a chain code, and the index of the child.
+//! This means that the participants don't need to run any protocol to derive keys.
+//!
+//! Because the tweak is public, and simply gets added to the private key,
+//! each participant can add it to their share of the key, getting a share
+//! of the derived key, with the same threshold.
+//! The same goes for presignatures: a presignature created with the parent key
+//! can be adjusted to sign with any derived key.
+//! This means that triples and presignatures can be generated in advance,
+//! before knowing which key will be used to sign.
+//!
+//! For `Secp256k1`, this matches public derivation in BIP32 exactly.
+//! Other curves with 32 byte scalars can use the same construction.
+use std::{error, fmt};
+
+use elliptic_curve::{
+    consts::U32,
+    sec1::{ModulusSize, ToEncodedPoint},
+    Field, FieldBytes, FieldBytesSize, Group, PrimeField,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+use crate::{
+    compat::CSCurve, KeygenOutput, PresignCommitments, PresignOutput, PresignShareCommitment,
+};
+
+/// A chain code, the extra entropy used to derive child keys.
+pub type ChainCode = [u8; 32];
+
+/// The offset at which child indices become hardened.
+///
+/// Hardened children can't be derived from public information, so we don't support them.
+pub const HARDENED_OFFSET: u32 = 1 << 31;
+
+/// Represents an error which can happen when deriving a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerivationError {
+    /// The path contained a hardened index.
+    Hardened(u32),
+    /// This index produces an invalid key, and the next index should be used instead.
+    ///
+    /// This happens with negligible probability.
+    InvalidChild(u32),
+}
+
+impl fmt::Display for DerivationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerivationError::Hardened(i) => write!(f, "index {} is hardened", i),
+            DerivationError::InvalidChild(i) => write!(f, "index {} produces an invalid key", i),
+        }
+    }
+}
+
+impl error::Error for DerivationError {}
+
+/// The result of deriving a child key.
+#[derive(Debug, Clone)]
+pub struct DerivedKey<C: CSCurve> {
+    /// The tweak to add to the parent private key, to get the child private key.
+    ///
+    /// This is the sum of the tweaks of each step in the path.
+    pub tweak: C::Scalar,
+    /// The child public key.
+    pub public_key: C::AffinePoint,
+    /// The chain code of the child, used to derive its own children.
+    pub chain_code: ChainCode,
+}
+
+/// Derive a child key, by following a path of non-hardened indices.
+///
+/// This only uses public information, and can be done by anyone knowing
+/// the parent public key, and its chain code.
+pub fn derive_key<C>(
+    public_key: &C::AffinePoint,
+    chain_code: &ChainCode,
+    path: &[u32],
+) -> Result<DerivedKey<C>, DerivationError>
+where
+    C: CSCurve<FieldBytesSize = U32>,
+    C::AffinePoint: ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let mut tweak = C::Scalar::ZERO;
+    let mut public_key = C::ProjectivePoint::from(*public_key);
+    let mut chain_code = *chain_code;
+    for &index in path {
+        if index >= HARDENED_OFFSET {
+            return Err(DerivationError::Hardened(index));
+        }
+        let affine: C::AffinePoint = public_key.into();
+        // Unwrapping is fine, since HMAC accepts keys of any size.
+        let mut mac = Hmac::<Sha512>::new_from_slice(&chain_code).unwrap();
+        mac.update(affine.to_encoded_point(true).as_bytes());
+        mac.update(&index.to_be_bytes());
+        let out = mac.finalize().into_bytes();
+        let (left, right) = out.split_at(32);
+
+        let mut step = FieldBytes::<C>::default();
+        step.copy_from_slice(left);
+        let step: C::Scalar =
+            Option::from(C::Scalar::from_repr(step)).ok_or(DerivationError::InvalidChild(index))?;
+        public_key += C::ProjectivePoint::generator() * step;
+        if public_key.is_identity().into() {
+            return Err(DerivationError::InvalidChild(index));
+        }
+        tweak += step;
+        chain_code.copy_from_slice(right);
+    }
+    Ok(DerivedKey {
+        tweak,
+        public_key: public_key.into(),
+        chain_code,
+    })
+}
+
+impl<C: CSCurve> KeygenOutput<C> {
+    /// Adjust this output to hold a share of the key with a tweak added to it.
+    ///
+    /// Every participant needs to use the same tweak, e.g. one from [derive_key].
+    pub fn with_tweak(&self, tweak: &C::Scalar) -> Self {
+        Self {
+            private_share: self.private_share + tweak,
+            public_key: (C::ProjectivePoint::from(self.public_key)
+                + C::ProjectivePoint::generator() * tweak)
+                .into(),
+            commitment: self.commitment.with_tweak(tweak),
+        }
+    }
+}
+
+impl<C: CSCurve> PresignOutput<C> {
+    /// Adjust this presignature to sign with a key that has a tweak added to it.
+    ///
+    /// This presignature must have been created with the original key,
+    /// and every participant needs to use the same tweak, e.g. one from [derive_key].
+    ///
+    /// Like any presignature, the result must only be used once,
+    /// and the original presignature can't be used anymore.
+    pub fn with_tweak(&self, tweak: &C::Scalar) -> Self {
+        // Since sigma = k * x, the presignature for x + tweak has sigma + k * tweak.
+        let tweak_point = |big_sigma: C::AffinePoint, big_k: C::AffinePoint| {
+            (C::ProjectivePoint::from(big_sigma) + C::ProjectivePoint::from(big_k) * tweak).into()
+        };
+        let commitments = self
+            .commitments
+            .as_ref()
+            .map(|commitments| PresignCommitments {
+                big_k: commitments.big_k,
+                big_sigma: tweak_point(commitments.big_sigma, commitments.big_k),
+                shares: commitments
+                    .shares
+                    .iter()
+                    .map(|share| PresignShareCommitment {
+                        participant: share.participant,
+                        big_k: share.big_k,
+                        big_sigma: tweak_point(share.big_sigma, share.big_k),
+                    })
+                    .collect(),
+            });
+        Self {
+            big_r: self.big_r,
+            k: self.k,
+            sigma: self.sigma + self.k * tweak,
+            commitments,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{elliptic_curve::sec1::FromEncodedPoint, AffinePoint, EncodedPoint, Secp256k1};
+
+    use super::*;
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decode_point(hex: &str) -> AffinePoint {
+        let encoded = EncodedPoint::from_bytes(decode_hex(hex)).unwrap();
+        AffinePoint::from_encoded_point(&encoded).unwrap()
+    }
+
+    fn decode_chain_code(hex: &str) -> ChainCode {
+        decode_hex(hex).try_into().unwrap()
+    }
+
+    #[test]
+    fn test_bip32_vectors() {
+        // Taken from the test vectors in BIP32, keeping only public derivation steps.
+        let vectors = [
+            // Test vector 1, from m/0H to m/0H/1.
+            (
+                "035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56",
+                "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141",
+                1,
+                "03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c",
+                "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19",
+            ),
+            // Test vector 2, from m to m/0.
+            (
+                "03cbcaa9c98c877a26977d00825c956a238e8dddfbd322cce4f74b0b5bd6ace4a7",
+                "60499f801b896d83179a4374aeb7822aaeaceaa0db1f85ee3e904c4defbd9689",
+                0,
+                "02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea",
+                "f0909affaa7ee7abe5dd4e100598d4dc53cd709d5a5c2cac40e7412f232f7c9c",
+            ),
+        ];
+        for (parent, parent_chain, index, child, child_chain) in vectors {
+            let derived = derive_key::<Secp256k1>(
+                &decode_point(parent),
+                &decode_chain_code(parent_chain),
+                &[index],
+            )
+            .unwrap();
+            assert_eq!(derived.public_key, decode_point(child));
+            assert_eq!(derived.chain_code, decode_chain_code(child_chain));
+        }
+    }
+
+    #[test]
+    fn test_derive_path_composes() {
+        let parent =
+            decode_point("035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56");
+        let chain_code = [7u8; 32];
+
+        let full = derive_key::<Secp256k1>(&parent, &chain_code, &[1, 2, 3]).unwrap();
+        let first = derive_key::<Secp256k1>(&parent, &chain_code, &[1]).unwrap();
+        let rest = derive_key::<Secp256k1>(&first.public_key, &first.chain_code, &[2, 3]).unwrap();
+        assert_eq!(full.public_key, rest.public_key);
+        assert_eq!(full.chain_code, rest.chain_code);
+        assert_eq!(full.tweak, first.tweak + rest.tweak);
+
+        let empty = derive_key::<Secp256k1>(&parent, &chain_code, &[]).unwrap();
+        assert_eq!(empty.public_key, parent);
+        assert_eq!(
+            derive_key::<Secp256k1>(&parent, &chain_code, &[1, HARDENED_OFFSET]).unwrap_err(),
+            DerivationError::Hardened(HARDENED_OFFSET)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! This module contains a protocol to reconstruct the private key from its shares.
+//!
+//! **WARNING** Reconstructing the private key defeats the purpose of sharing it
+//! in the first place: whoever receives it can sign anything on their own.
+//! This should only be used for disaster recovery, which is why this module
+//! is only available with the `key-export` feature.
+use elliptic_curve::{Field, Group, ScalarPrimitive};
+use zeroize::Zeroizing;
+
+use crate::{
+    compat::CSCurve,
+    participants::{ParticipantList, ParticipantMap},
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        InitializationError, Misbehavior, Participant, Protocol, ProtocolError, ProtocolId,
+    },
+    KeygenOutput,
+};
+
+async fn do_export_key<C: CSCurve>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    recipient: Participant,
+    keygen_out: KeygenOutput<C>,
+) -> Result<Option<C::Scalar>, ProtocolError> {
+    let wait0 = chan.next_waitpoint();
+    if me != recipient {
+        let share: Zeroizing<ScalarPrimitive<C>> = Zeroizing::new(keygen_out.private_share.into());
+        chan.send_private(wait0, recipient, &*share).await;
+        return Ok(None);
+    }
+
+    // The shares are wiped once the key is rebuilt, or if we fail before that.
+    let mut shares = Zeroizing::new(ParticipantMap::new(&participants));
+    shares.put(me, keygen_out.private_share);
+    while !shares.full() {
+        let (from, share): (_, ScalarPrimitive<C>) = chan.recv(wait0, shares.missing()).await?;
+        let share = Zeroizing::new(C::Scalar::from(share));
+        if !keygen_out.commitment.verify_share(from, &share) {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadPrivateShare,
+            });
+        }
+        shares.put(from, *share);
+    }
+
+    let mut x = Zeroizing::new(C::Scalar::ZERO);
+    for p in participants.others(me).chain(std::iter::once(me)) {
+        *x += participants.lagrange::<C>(p) * shares[p];
+    }
+    if C::ProjectivePoint::generator() * *x != C::ProjectivePoint::from(keygen_out.public_key) {
+        return Err(ProtocolError::AssertionFailed(
+            "reconstructed private key does not match public key".to_string(),
+        ));
+    }
+    Ok(Some(*x))
+}
+
+/// The key export protocol, reconstructing the private key for a single recipient.
+///
+/// **WARNING** This gives the recipient the entire private key, allowing them
+/// to sign anything on their own, which defeats the purpose of threshold signing.
+/// This should only be used for disaster recovery, and the private key should
+/// be wiped from memory as soon as possible.
+///
+/// Every participant sends their share privately to the `recipient`,
+/// who must also be a participant, and there need to be at least as many
+/// participants as the threshold of the key. The recipient checks each share against the key commitment,
+/// blaming any participant sending a bad share, then reconstructs the private key,
+/// checking it against the public key, and returns it.
+/// Every other participant returns `None`.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+pub fn export_key<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    recipient: Participant,
+    keygen_out: KeygenOutput<C>,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = Option<C::Scalar>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    if participants.len() < keygen_out.commitment.threshold() {
+        return Err(InitializationError::BadParameters(
+            "participant count must be >= threshold".to_string(),
+        ));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    if !participants.contains(recipient) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain the recipient".to_string(),
+        ));
+    }
+
+    let ctx = Context::new(ProtocolId::ExportKey, session_id);
+    let fut = do_export_key(
+        ctx.shared_channel(),
+        participants,
+        me,
+        recipient,
+        keygen_out,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::{Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{protocol::run_protocol, split_key};
+
+    #[allow(clippy::type_complexity)]
+    fn run_export(
+        participants: &[Participant],
+        recipient: Participant,
+        shares: Vec<KeygenOutput<Secp256k1>>,
+    ) -> Result<Vec<(Participant, Option<Scalar>)>, Box<dyn Error>> {
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Option<Scalar>>>)> =
+            Vec::with_capacity(participants.len());
+        for (p, keygen_out) in participants.iter().zip(shares) {
+            let protocol = export_key(participants, *p, recipient, keygen_out, b"test export")?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        Ok(run_protocol(protocols)?)
+    }
+
+    #[test]
+    fn test_export_key() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let secret = Scalar::random(&mut OsRng);
+        let shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, threshold).unwrap();
+
+        // Only a threshold of participants is needed.
+        let recipient = participants[2];
+        let result = run_export(&participants[1..], recipient, shares[1..].to_vec())?;
+        for (p, out) in result {
+            if p == recipient {
+                assert_eq!(out, Some(secret));
+            } else {
+                assert_eq!(out, None);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_key_blames_bad_share() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let secret = Scalar::random(&mut OsRng);
+        let mut shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, 2).unwrap();
+        shares[1].private_share += Scalar::ONE;
+
+        match run_export(&participants, participants[0], shares) {
+            Err(e) => match e.downcast_ref::<ProtocolError>() {
+                Some(ProtocolError::Misbehaved {
+                    participant,
+                    reason,
+                }) => {
+                    assert_eq!(*participant, participants[1]);
+                    assert_eq!(*reason, Misbehavior::BadPrivateShare);
+                }
+                _ => panic!("expected the bad share to be blamed"),
+            },
+            Ok(_) => panic!("expected the export to fail"),
+        }
+    }
+
+    #[test]
+    fn test_export_key_checks_threshold() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let secret = Scalar::random(&mut OsRng);
+        let shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, 3).unwrap();
+
+        // Fewer participants than the threshold of the key can't rebuild it.
+        let result = export_key(
+            &participants[..2],
+            participants[0],
+            participants[0],
+            shares[0].clone(),
+            b"test export",
+        );
+        assert!(matches!(result, Err(InitializationError::BadParameters(_))));
+    }
+}
@@ -1,30 +1,52 @@
 use elliptic_curve::{Field, Group, ScalarPrimitive};
 use magikitten::Transcript;
-use rand_core::OsRng;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::compat::CSCurve;
-use crate::crypto::{commit, hash, Digest};
+use crate::crypto::{commit, hash, Digest, Randomizer};
 use crate::math::{GroupPolynomial, Polynomial};
 use crate::participants::{ParticipantCounter, ParticipantList, ParticipantMap};
 use crate::proofs::dlog;
-use crate::protocol::internal::{make_protocol, Context, SharedChannel};
-use crate::protocol::{InitializationError, Participant, Protocol, ProtocolError};
-use crate::serde::encode;
+use crate::protocol::internal::{make_protocol, Context};
+use crate::protocol::{
+    InitializationError, Misbehavior, Participant, Protocol, ProtocolError, ProtocolId,
+};
+use crate::serde::{
+    deserialize_affine_point, deserialize_scalar, encode, serialize_affine_point, serialize_scalar,
+};
 
 const LABEL: &[u8] = b"cait-sith v0.8.0 keygen";
 
-async fn do_keyshare<C: CSCurve>(
-    mut chan: SharedChannel,
+/// The most keys which can be generated by a single run of the protocol.
+const MAX_KEY_COUNT: usize = 1 << 10;
+
+/// The data used to fork the transcript, for a proof from a participant about a given key.
+fn fork_data(p: Participant, i: usize) -> [u8; 12] {
+    let mut out = [0u8; 12];
+    out[..4].copy_from_slice(&p.bytes());
+    out[4..].copy_from_slice(&u64::try_from(i).unwrap().to_le_bytes());
+    out
+}
+
+/// The polynomials, commitment opening, and proofs each participant reveals for a batch.
+type Reveal<C> = (Vec<GroupPolynomial<C>>, Randomizer, Vec<dlog::Proof<C>>);
+
+async fn do_keyshare_many<C: CSCurve>(
+    ctx: Context<'_>,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
-    s_i: C::Scalar,
-    big_s: Option<C::ProjectivePoint>,
-) -> Result<(C::Scalar, C::AffinePoint), ProtocolError> {
-    let mut rng = OsRng;
+    s_i: Zeroizing<Vec<C::Scalar>>,
+) -> Result<Vec<KeygenOutput<C>>, ProtocolError> {
+    let mut chan = ctx.shared_channel();
+    let mut rng = chan.rng(b"keyshare");
     let mut transcript = Transcript::new(LABEL);
+    let count = s_i.len();
 
     // Spec 1.2
+    transcript.message(b"session id", ctx.session_id());
     transcript.message(b"group", C::NAME);
     transcript.message(b"participants", &encode(&participants));
     // To allow interop between platforms where usize is different!
@@ -32,12 +54,17 @@ async fn do_keyshare<C: CSCurve>(
         b"threshold",
         &u64::try_from(threshold).unwrap().to_be_bytes(),
     );
+    transcript.message(b"count", &u64::try_from(count).unwrap().to_be_bytes());
 
     // Spec 1.3
-    let f: Polynomial<C> = Polynomial::extend_random(&mut rng, threshold, &s_i);
+    let f: Vec<Polynomial<C>> = s_i
+        .iter()
+        .map(|s_i_k| Polynomial::extend_random(&mut rng, threshold, s_i_k))
+        .collect();
+    drop(s_i);
 
     // Spec 1.4
-    let mut big_f = f.commit();
+    let mut big_f: Vec<GroupPolynomial<C>> = f.iter().map(|f_k| f_k.commit()).collect();
 
     // Spec 1.5
     let (my_commitment, my_randomizer) = commit(&mut rng, &big_f);
@@ -50,7 +77,7 @@ async fn do_keyshare<C: CSCurve>(
     let mut all_commitments = ParticipantMap::new(&participants);
     all_commitments.put(me, my_commitment);
     while !all_commitments.full() {
-        let (from, commitment) = chan.recv(wait0).await?;
+        let (from, commitment) = chan.recv(wait0, all_commitments.missing()).await?;
         all_commitments.put(from, commitment);
     }
 
@@ -65,139 +92,280 @@ async fn do_keyshare<C: CSCurve>(
     chan.send_many(wait1, &my_confirmation).await;
 
     // Spec 2.5
-    let statement = dlog::Statement::<C> {
-        public: &big_f.evaluate_zero(),
-    };
-    let witness = dlog::Witness::<C> {
-        x: &f.evaluate_zero(),
-    };
-    let my_phi_proof = dlog::prove(
-        &mut rng,
-        &mut transcript.forked(b"dlog0", &me.bytes()),
-        statement,
-        witness,
-    );
+    let my_phi_proofs: Vec<dlog::Proof<C>> = f
+        .iter()
+        .zip(big_f.iter())
+        .enumerate()
+        .map(|(k, (f_k, big_f_k))| {
+            let statement = dlog::Statement::<C> {
+                public: &big_f_k.evaluate_zero(),
+            };
+            let witness = dlog::Witness::<C> {
+                x: &f_k.evaluate_zero(),
+            };
+            dlog::prove(
+                &mut rng,
+                &mut transcript.forked(b"dlog0", &fork_data(me, k)),
+                statement,
+                witness,
+            )
+        })
+        .collect();
 
     // Spec 2.6
     let wait2 = chan.next_waitpoint();
-    chan.send_many(wait2, &(&big_f, &my_randomizer, my_phi_proof))
+    chan.send_many(wait2, &(&big_f, &my_randomizer, &my_phi_proofs))
         .await;
 
     // Spec 2.7
     let wait3 = chan.next_waitpoint();
     for p in participants.others(me) {
-        let x_i_j: ScalarPrimitive<C> = f.evaluate(&p.scalar::<C>()).into();
+        let mut x_i_j: Vec<ScalarPrimitive<C>> = f
+            .iter()
+            .map(|f_k| f_k.evaluate(&p.scalar::<C>()).into())
+            .collect();
         chan.send_private(wait3, p, &x_i_j).await;
+        x_i_j.zeroize();
     }
-    let mut x_i = f.evaluate(&me.scalar::<C>());
+    let mut x_i: Zeroizing<Vec<_>> = Zeroizing::new(
+        f.iter()
+            .map(|f_k| f_k.evaluate(&me.scalar::<C>()))
+            .collect(),
+    );
 
     // Spec 3.1 + 3.2
     let mut seen = ParticipantCounter::new(&participants);
     seen.put(me);
     while !seen.full() {
-        let (from, confirmation): (_, Digest) = chan.recv(wait1).await?;
+        let (from, confirmation): (_, Digest) = chan.recv(wait1, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
         if confirmation != my_confirmation {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "confirmation from {from:?} did not match expectation"
-            )));
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::InconsistentConfirmation,
+            });
         }
     }
 
     // Spec 3.3 + 3.4, and also part of 3.6, for summing up the Fs.
+    let mut all_big_f = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
-        let (from, (their_big_f, their_randomizer, their_phi_proof)): (
-            _,
-            (GroupPolynomial<C>, _, _),
-        ) = chan.recv(wait2).await?;
+        let (from, (their_big_f, their_randomizer, their_phi_proofs)): (_, Reveal<C>) =
+            chan.recv(wait2, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
 
-        if their_big_f.len() != threshold {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "polynomial from {from:?} has the wrong length"
-            )));
+        if their_big_f.len() != count
+            || their_phi_proofs.len() != count
+            || their_big_f.iter().any(|f_k| f_k.len() != threshold)
+        {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadPolynomial,
+            });
         }
         if !all_commitments[from].check(&their_big_f, &their_randomizer) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "commitment from {from:?} did not match revealed F"
-            )));
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadCommitmentOpening,
+            });
         }
-        let statement = dlog::Statement::<C> {
-            public: &their_big_f.evaluate_zero(),
-        };
-        if !dlog::verify(
-            &mut transcript.forked(b"dlog0", &from.bytes()),
-            statement,
-            &their_phi_proof,
-        ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+        for (k, (their_big_f_k, their_phi_proof)) in
+            their_big_f.iter().zip(their_phi_proofs.iter()).enumerate()
+        {
+            let statement = dlog::Statement::<C> {
+                public: &their_big_f_k.evaluate_zero(),
+            };
+            if !dlog::verify(
+                &mut transcript.forked(b"dlog0", &fork_data(from, k)),
+                statement,
+                their_phi_proof,
+            ) {
+                return Err(ProtocolError::Misbehaved {
+                    participant: from,
+                    reason: Misbehavior::BadDlogProof,
+                });
+            }
         }
-        big_f += &their_big_f;
+        for (big_f_k, their_big_f_k) in big_f.iter_mut().zip(their_big_f.iter()) {
+            *big_f_k += their_big_f_k;
+        }
+        all_big_f.put(from, their_big_f);
     }
 
     // Spec 3.5 + 3.6
+    let mut all_x_j_i = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
-        let (from, x_j_i): (_, ScalarPrimitive<C>) = chan.recv(wait3).await?;
+        let (from, mut x_j_i): (_, Vec<ScalarPrimitive<C>>) =
+            chan.recv(wait3, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
-        x_i += C::Scalar::from(x_j_i);
+        if x_j_i.len() != count {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadPrivateShare,
+            });
+        }
+        let x_j_i_scalars: Zeroizing<Vec<C::Scalar>> =
+            Zeroizing::new(x_j_i.iter().map(|x| C::Scalar::from(*x)).collect());
+        x_j_i.zeroize();
+        for (x_i_k, x_j_i_k) in x_i.iter_mut().zip(x_j_i_scalars.iter()) {
+            *x_i_k += x_j_i_k;
+        }
+        all_x_j_i.put(from, x_j_i_scalars);
     }
 
     // Spec 3.7
-    if big_f.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * x_i {
-        return Err(ProtocolError::AssertionFailed(
-            "received bad private share".to_string(),
-        ));
+    for (k, (big_f_k, x_i_k)) in big_f.iter().zip(x_i.iter()).enumerate() {
+        if big_f_k.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * x_i_k {
+            // Only now do we check each share individually, to find out who sent a bad one.
+            for from in participants.others(me) {
+                if all_big_f[from][k].evaluate(&me.scalar::<C>())
+                    != C::ProjectivePoint::generator() * all_x_j_i[from][k]
+                {
+                    return Err(ProtocolError::Misbehaved {
+                        participant: from,
+                        reason: Misbehavior::BadPrivateShare,
+                    });
+                }
+            }
+            return Err(ProtocolError::AssertionFailed(
+                "received bad private share".to_string(),
+            ));
+        }
     }
 
+    // Spec 3.9
+    Ok(big_f
+        .into_iter()
+        .zip(x_i.iter())
+        .map(|(big_f_k, x_i_k)| KeygenOutput {
+            private_share: *x_i_k,
+            public_key: big_f_k.evaluate_zero().into(),
+            commitment: KeyCommitment(big_f_k),
+        })
+        .collect())
+}
+
+async fn do_keyshare<C: CSCurve>(
+    ctx: Context<'_>,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    s_i: C::Scalar,
+    big_s: Option<C::ProjectivePoint>,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let mut out =
+        do_keyshare_many::<C>(ctx, participants, me, threshold, Zeroizing::new(vec![s_i])).await?;
+    // Unwrapping is fine, since we asked for exactly one key.
+    let out = out.pop().unwrap();
+
     // Spec 3.8
-    let big_x = big_f.evaluate_zero();
     match big_s {
-        Some(big_s) if big_s != big_x => {
-            return Err(ProtocolError::AssertionFailed(
-                "new public key does not match old public key".to_string(),
-            ))
-        }
-        _ => {}
-    };
+        Some(big_s) if big_s != out.public_key.into() => Err(ProtocolError::AssertionFailed(
+            "new public key does not match old public key".to_string(),
+        )),
+        _ => Ok(out),
+    }
+}
 
-    // Spec 3.9
-    Ok((x_i, big_x.into()))
+/// A public commitment to the polynomial sharing a private key.
+///
+/// Evaluating this polynomial at a participant gives their *public share*,
+/// i.e. their share of the private key, times the generator.
+/// This lets anybody check the share of an individual participant,
+/// or values derived from it, without knowing the private key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "C: CSCurve")]
+pub struct KeyCommitment<C: CSCurve>(GroupPolynomial<C>);
+
+impl<C: CSCurve> KeyCommitment<C> {
+    /// Commit to a polynomial, evaluating to each share, with the private key at zero.
+    pub(crate) fn from_polynomial(f: &Polynomial<C>) -> Self {
+        Self(f.commit())
+    }
+
+    /// The public key, which is the commitment evaluated at zero.
+    pub fn public_key(&self) -> C::AffinePoint {
+        self.0.evaluate_zero().into()
+    }
+
+    /// The public share of a participant.
+    pub fn public_share(&self, participant: Participant) -> C::AffinePoint {
+        self.0.evaluate(&participant.scalar::<C>()).into()
+    }
+
+    /// The threshold of the sharing, i.e. how many shares are needed to get the key.
+    pub fn threshold(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check that a share of the private key belongs to a given participant.
+    #[must_use]
+    pub fn verify_share(&self, participant: Participant, share: &C::Scalar) -> bool {
+        self.0.evaluate(&participant.scalar::<C>()) == C::ProjectivePoint::generator() * share
+    }
+
+    /// Add a tweak to the committed private key, and thus to every share.
+    pub(crate) fn with_tweak(&self, tweak: &C::Scalar) -> Self {
+        let mut out = self.0.clone();
+        out.set_zero(self.0.evaluate_zero() + C::ProjectivePoint::generator() * tweak);
+        Self(out)
+    }
 }
 
 /// Represents the output of the key generation protocol.
 ///
-/// This contains our share of the private key, along with the public key.
-#[derive(Debug, Clone)]
+/// This contains our share of the private key, along with the public key,
+/// and a commitment to everyone's share.
+///
+/// The private share is wiped from memory when this is dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(bound = "C: CSCurve")]
 pub struct KeygenOutput<C: CSCurve> {
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub private_share: C::Scalar,
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
+    #[zeroize(skip)]
     pub public_key: C::AffinePoint,
+    #[zeroize(skip)]
+    pub commitment: KeyCommitment<C>,
 }
 
 async fn do_keygen<C: CSCurve>(
-    chan: SharedChannel,
+    ctx: Context<'_>,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
 ) -> Result<KeygenOutput<C>, ProtocolError> {
-    let s_i = C::Scalar::random(&mut OsRng);
-    let (private_share, public_key) =
-        do_keyshare::<C>(chan, participants, me, threshold, s_i, None).await?;
-    Ok(KeygenOutput {
-        private_share,
-        public_key,
-    })
+    let s_i = Zeroizing::new(C::Scalar::random(&mut ctx.rng(b"keygen secret")));
+    do_keyshare::<C>(ctx, participants, me, threshold, *s_i, None).await
+}
+
+async fn do_keygen_many<C: CSCurve>(
+    ctx: Context<'_>,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    count: usize,
+) -> Result<Vec<KeygenOutput<C>>, ProtocolError> {
+    let mut rng = ctx.rng(b"keygen secret");
+    let s_i = Zeroizing::new((0..count).map(|_| C::Scalar::random(&mut rng)).collect());
+    do_keyshare_many::<C>(ctx, participants, me, threshold, s_i).await
 }
 
 /// The key generation protocol, with a given threshold.
@@ -208,10 +376,14 @@ async fn do_keygen<C: CSCurve>(
 ///
 /// This needs to be run once, before then being able to perform threshold
 /// signatures using the key.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
 pub fn keygen<C: CSCurve>(
     participants: &[Participant],
     me: Participant,
     threshold: usize,
+    session_id: &[u8],
 ) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
@@ -236,27 +408,221 @@ pub fn keygen<C: CSCurve>(
         ));
     }
 
-    let ctx = Context::new();
-    let fut = do_keygen(ctx.shared_channel(), participants, me, threshold);
+    let ctx = Context::new(ProtocolId::Keygen, session_id);
+    let fut = do_keygen(ctx.clone(), participants, me, threshold);
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Generate many independent keys at once, with a given threshold.
+///
+/// This is like [keygen], except that `count` keys are produced
+/// by a single run of the protocol, which shares the rounds of communication
+/// across the whole batch. At most 1024 keys can be generated at once.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+pub fn keygen_many<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    count: usize,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = Vec<KeygenOutput<C>>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    // Spec 1.1
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+    if count == 0 {
+        return Err(InitializationError::BadParameters(
+            "key count must be > 0".to_string(),
+        ));
+    }
+    if count > MAX_KEY_COUNT {
+        return Err(InitializationError::BadParameters(format!(
+            "key count must be <= {}",
+            MAX_KEY_COUNT
+        )));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let ctx = Context::new(ProtocolId::KeygenMany, session_id);
+    let fut = do_keygen_many(ctx.clone(), participants, me, threshold, count);
+    Ok(make_protocol(ctx, fut))
+}
+
+async fn do_import_key<C: CSCurve>(
+    ctx: Context<'_>,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    secret: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let s_i = Zeroizing::new(secret.unwrap_or(C::Scalar::ZERO));
+    do_keyshare::<C>(
+        ctx,
+        participants,
+        me,
+        threshold,
+        *s_i,
+        Some(public_key.into()),
+    )
+    .await
+}
+
+/// The key import protocol, sharing an existing private key.
+///
+/// This is like key generation, except that instead of creating a new key,
+/// the `dealer` shares an existing private key among the participants.
+/// The dealer passes this private key as `secret`, and every other participant
+/// passes `None`.
+/// Every participant needs to know the public key, which is checked against
+/// the result of the protocol.
+///
+/// The dealer still knows the entire private key after this protocol,
+/// and should delete it, if the goal is for no single party to hold the key.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+#[allow(clippy::too_many_arguments)]
+pub fn import_key<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    dealer: Participant,
+    secret: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    if !participants.contains(dealer) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain the dealer".to_string(),
+        ));
+    }
+
+    match (me == dealer, &secret) {
+        (true, None) => {
+            return Err(InitializationError::BadParameters(
+                "the dealer must provide the secret".to_string(),
+            ))
+        }
+        (false, Some(_)) => {
+            return Err(InitializationError::BadParameters(
+                "only the dealer can provide a secret".to_string(),
+            ))
+        }
+        (true, Some(secret)) => {
+            if C::ProjectivePoint::generator() * secret != C::ProjectivePoint::from(public_key) {
+                return Err(InitializationError::BadParameters(
+                    "secret does not match the public key".to_string(),
+                ));
+            }
+        }
+        (false, None) => {}
+    }
+
+    let ctx = Context::new(ProtocolId::ImportKey, session_id);
+    let fut = do_import_key(ctx.clone(), participants, me, threshold, secret, public_key);
     Ok(make_protocol(ctx, fut))
 }
 
+/// Split an existing private key into shares, as a trusted dealer.
+///
+/// This returns the output of key generation for each participant,
+/// in the same order as the list of participants.
+///
+/// Unlike [import_key], this requires trusting the person running this code
+/// to deliver each share privately, and then forget about them.
+pub fn split_key<C: CSCurve>(
+    rng: &mut impl CryptoRngCore,
+    secret: &C::Scalar,
+    participants: &[Participant],
+    threshold: usize,
+) -> Result<Vec<KeygenOutput<C>>, InitializationError> {
+    if threshold == 0 {
+        return Err(InitializationError::BadParameters(
+            "threshold must be > 0".to_string(),
+        ));
+    }
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+    if ParticipantList::new(participants).is_none() {
+        return Err(InitializationError::BadParameters(
+            "participant list cannot contain duplicates".to_string(),
+        ));
+    }
+
+    let f = Polynomial::<C>::extend_random(rng, threshold, secret);
+    let commitment = KeyCommitment::from_polynomial(&f);
+    let public_key = commitment.public_key();
+    Ok(participants
+        .iter()
+        .map(|p| KeygenOutput {
+            private_share: f.evaluate(&p.scalar::<C>()),
+            public_key,
+            commitment: commitment.clone(),
+        })
+        .collect())
+}
+
 async fn do_reshare<C: CSCurve>(
-    chan: SharedChannel,
+    ctx: Context<'_>,
     participants: ParticipantList,
     old_subset: ParticipantList,
     me: Participant,
     threshold: usize,
     my_share: Option<C::Scalar>,
     public_key: C::AffinePoint,
-) -> Result<C::Scalar, ProtocolError> {
-    let s_i = my_share
-        .map(|x_i| old_subset.lagrange::<C>(me) * x_i)
-        .unwrap_or(C::Scalar::ZERO);
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let s_i = Zeroizing::new(
+        my_share
+            .map(|x_i| old_subset.lagrange::<C>(me) * x_i)
+            .unwrap_or(C::Scalar::ZERO),
+    );
     let big_s: C::ProjectivePoint = public_key.into();
-    let (private_share, _) =
-        do_keyshare::<C>(chan, participants, me, threshold, s_i, Some(big_s)).await?;
-    Ok(private_share)
+    do_keyshare::<C>(ctx, participants, me, threshold, *s_i, Some(big_s)).await
 }
 
 /// The resharing protocol.
@@ -268,7 +634,12 @@ async fn do_reshare<C: CSCurve>(
 /// so that the old key can be reconstructed.
 ///
 /// This protocol creates fresh shares for every party, without revealing the key,
-/// of course. The output of the protocol is the new share for this party.
+/// of course. The output of the protocol is the new share for this party,
+/// along with the same public key, and a commitment to the new shares.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+#[allow(clippy::too_many_arguments)]
 pub fn reshare<C: CSCurve>(
     old_participants: &[Participant],
     old_threshold: usize,
@@ -277,7 +648,8 @@ pub fn reshare<C: CSCurve>(
     me: Participant,
     my_share: Option<C::Scalar>,
     public_key: C::AffinePoint,
-) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
     if new_participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
             "participant count cannot be < 2, found: {}",
@@ -322,9 +694,9 @@ pub fn reshare<C: CSCurve>(
         ));
     }
 
-    let ctx = Context::new();
+    let ctx = Context::new(ProtocolId::Reshare, session_id);
     let fut = do_reshare::<C>(
-        ctx.shared_channel(),
+        ctx.clone(),
         new_participants,
         old_subset,
         me,
@@ -339,13 +711,17 @@ pub fn reshare<C: CSCurve>(
 ///
 /// This is like resharing, but with extra constraints to ensure that the set
 /// of participants and threshold do not change.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
 pub fn refresh<C: CSCurve>(
     participants: &[Participant],
     threshold: usize,
     me: Participant,
     my_share: C::Scalar,
     public_key: C::AffinePoint,
-) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
     reshare::<C>(
         participants,
         threshold,
@@ -354,6 +730,7 @@ pub fn refresh<C: CSCurve>(
         me,
         Some(my_share),
         public_key,
+        session_id,
     )
 }
 
@@ -362,9 +739,10 @@ mod test {
     use std::error::Error;
 
     use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
 
     use super::*;
-    use crate::protocol::{run_protocol, Participant};
+    use crate::protocol::{run_protocol, Action, Corrupt, Participant};
 
     #[allow(clippy::type_complexity)]
     fn do_keygen(
@@ -377,7 +755,7 @@ mod test {
         )> = Vec::with_capacity(participants.len());
 
         for p in participants.iter() {
-            let protocol = keygen(participants, *p, threshold)?;
+            let protocol = keygen(participants, *p, threshold, b"test keygen")?;
             protocols.push((*p, Box::new(protocol)));
         }
 
@@ -398,11 +776,26 @@ mod test {
         assert!(result.len() == participants.len());
         assert_eq!(result[0].1.public_key, result[1].1.public_key);
         assert_eq!(result[1].1.public_key, result[2].1.public_key);
+        assert_eq!(result[0].1.commitment, result[1].1.commitment);
+        assert_eq!(result[1].1.commitment, result[2].1.commitment);
+        assert_eq!(result[0].1.commitment.threshold(), threshold);
 
         let pub_key = result[2].1.public_key;
+        assert_eq!(result[0].1.commitment.public_key(), pub_key);
+        for (p, out) in &result {
+            assert!(out.commitment.verify_share(*p, &out.private_share));
+            assert_eq!(
+                out.commitment.public_share(*p),
+                (ProjectivePoint::GENERATOR * out.private_share).to_affine()
+            );
+        }
+        assert!(!result[0]
+            .1
+            .commitment
+            .verify_share(result[1].0, &result[0].1.private_share));
 
         let participants = vec![result[0].0, result[1].0, result[2].0];
-        let shares = vec![
+        let shares = [
             result[0].1.private_share,
             result[1].1.private_share,
             result[2].1.private_share,
@@ -416,6 +809,61 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_keygen_many() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let count = 3;
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<KeygenOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let protocol = keygen_many(&participants, p, threshold, count, b"test keygen many")?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        let mut public_keys = Vec::with_capacity(count);
+        for k in 0..count {
+            let shares: Vec<_> = result
+                .iter()
+                .map(|(p, outs)| (*p, outs[k].clone()))
+                .collect();
+            let public_key = shares[0].1.public_key;
+            for (p, out) in &shares {
+                assert_eq!(out.public_key, public_key);
+                assert_eq!(out.commitment, shares[0].1.commitment);
+                assert!(out.commitment.verify_share(*p, &out.private_share));
+            }
+            assert_eq!(
+                ProjectivePoint::GENERATOR * reconstruct(&shares[1..]),
+                public_key
+            );
+            public_keys.push(public_key);
+        }
+        // Each key should be independent of the others.
+        assert_ne!(public_keys[0], public_keys[1]);
+        assert_ne!(public_keys[1], public_keys[2]);
+
+        assert!(keygen_many::<Secp256k1>(&participants, participants[0], 2, 0, b"").is_err());
+        assert!(keygen_many::<Secp256k1>(
+            &participants,
+            participants[0],
+            2,
+            MAX_KEY_COUNT + 1,
+            b""
+        )
+        .is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_refresh() -> Result<(), Box<dyn Error>> {
         let participants = vec![
@@ -430,8 +878,11 @@ mod test {
         let pub_key = result0[2].1.public_key;
 
         // Refresh
-        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> =
-            Vec::with_capacity(participants.len());
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
 
         for (p, out) in result0.iter() {
             let protocol = refresh::<Secp256k1>(
@@ -440,6 +891,7 @@ mod test {
                 *p,
                 out.private_share,
                 out.public_key,
+                b"test refresh",
             )?;
             protocols.push((*p, Box::new(protocol)));
         }
@@ -447,7 +899,11 @@ mod test {
         let result1 = run_protocol(protocols)?;
 
         let participants = vec![result1[0].0, result1[1].0, result1[2].0];
-        let shares = vec![result1[0].1, result1[1].1, result1[2].1];
+        let shares = [
+            result1[0].1.private_share,
+            result1[1].1.private_share,
+            result1[2].1.private_share,
+        ];
         let p_list = ParticipantList::new(&participants).unwrap();
         let x = p_list.lagrange::<Secp256k1>(participants[0]) * shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * shares[1]
@@ -479,8 +935,11 @@ mod test {
             .collect();
         setup.push((Participant::from(3u32), (None, pub_key)));
 
-        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> =
-            Vec::with_capacity(participants.len());
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
 
         for (p, out) in setup.iter() {
             let protocol = reshare::<Secp256k1>(
@@ -491,14 +950,26 @@ mod test {
                 *p,
                 out.0,
                 out.1,
+                b"test reshare",
             )?;
             protocols.push((*p, Box::new(protocol)));
         }
 
         let result1 = run_protocol(protocols)?;
+        for (p, out) in &result1 {
+            assert_eq!(out.public_key, pub_key);
+            assert_eq!(out.commitment, result1[0].1.commitment);
+            assert_eq!(out.commitment.threshold(), threshold1);
+            assert!(out.commitment.verify_share(*p, &out.private_share));
+        }
 
         let participants = vec![result1[0].0, result1[1].0, result1[2].0, result1[3].0];
-        let shares = vec![result1[0].1, result1[1].1, result1[2].1, result1[3].1];
+        let shares = [
+            result1[0].1.private_share,
+            result1[1].1.private_share,
+            result1[2].1.private_share,
+            result1[3].1.private_share,
+        ];
         let p_list = ParticipantList::new(&participants).unwrap();
         let x = p_list.lagrange::<Secp256k1>(participants[0]) * shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * shares[1]
@@ -508,4 +979,155 @@ mod test {
 
         Ok(())
     }
+
+    /// Reconstruct the private key from a threshold of shares.
+    fn reconstruct(shares: &[(Participant, KeygenOutput<Secp256k1>)]) -> Scalar {
+        let participants: Vec<_> = shares.iter().map(|(p, _)| *p).collect();
+        let p_list = ParticipantList::new(&participants).unwrap();
+        shares
+            .iter()
+            .map(|(p, out)| p_list.lagrange::<Secp256k1>(*p) * out.private_share)
+            .sum()
+    }
+
+    #[test]
+    fn test_import_key() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let dealer = participants[1];
+        let secret = Scalar::random(&mut OsRng);
+        let public_key = (ProjectivePoint::GENERATOR * secret).to_affine();
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let my_secret = if p == dealer { Some(secret) } else { None };
+            let protocol = import_key(
+                &participants,
+                p,
+                threshold,
+                dealer,
+                my_secret,
+                public_key,
+                b"test import",
+            )?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+        assert!(result.iter().all(|(_, out)| out.public_key == public_key));
+        assert_eq!(reconstruct(&result[..threshold]), secret);
+        assert_eq!(reconstruct(&result[1..]), secret);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_key_checks_arguments() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let secret = Scalar::random(&mut OsRng);
+        let public_key = (ProjectivePoint::GENERATOR * secret).to_affine();
+        let other_key = (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).to_affine();
+        let (p0, p1) = (participants[0], participants[1]);
+
+        let import = |me, dealer, secret, public_key| {
+            import_key::<Secp256k1>(&participants, me, 2, dealer, secret, public_key, b"").is_ok()
+        };
+        assert!(import(p0, p0, Some(secret), public_key));
+        assert!(import(p1, p0, None, public_key));
+        assert!(!import(p0, p0, None, public_key));
+        assert!(!import(p1, p0, Some(secret), public_key));
+        assert!(!import(p0, p0, Some(secret), other_key));
+        assert!(!import(p0, Participant::from(2u32), None, public_key));
+    }
+
+    #[test]
+    fn test_split_key() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let secret = Scalar::random(&mut OsRng);
+
+        let shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, threshold).unwrap();
+        let shares: Vec<_> = participants.iter().copied().zip(shares).collect();
+        let public_key = (ProjectivePoint::GENERATOR * secret).to_affine();
+        assert!(shares.iter().all(|(_, out)| out.public_key == public_key));
+        assert_eq!(reconstruct(&shares[..threshold]), secret);
+        assert_eq!(reconstruct(&shares[1..]), secret);
+        assert_ne!(reconstruct(&shares[..1]), secret);
+
+        let split = |participants: &[Participant], threshold| {
+            split_key::<Secp256k1>(&mut OsRng, &secret, participants, threshold).is_ok()
+        };
+        assert!(!split(&participants, 0));
+        assert!(!split(&participants, 4));
+        assert!(!split(
+            &[participants[0], participants[1], participants[0]],
+            2
+        ));
+    }
+
+    #[test]
+    fn test_keygen_output_zeroize() {
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, 2);
+        let private_share = f.evaluate(&Scalar::ONE);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let mut out = KeygenOutput::<Secp256k1> {
+            private_share,
+            public_key,
+            commitment: KeyCommitment::from_polynomial(&f),
+        };
+        out.zeroize();
+        assert_eq!(out.private_share, Scalar::ZERO);
+        assert_eq!(out.public_key, public_key);
+        assert_eq!(out.commitment.public_key(), public_key);
+    }
+
+    #[test]
+    fn test_keygen_blames_bad_share() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let cheater = participants[1];
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let protocol = keygen(&participants, p, 3, b"test keygen blame").unwrap();
+            if p == cheater {
+                let protocol = Corrupt {
+                    protocol,
+                    should_corrupt: |action: &Action<_>| matches!(action, Action::SendPrivate(..)),
+                };
+                protocols.push((p, Box::new(protocol)));
+            } else {
+                protocols.push((p, Box::new(protocol)));
+            }
+        }
+
+        match run_protocol(protocols) {
+            Err(ProtocolError::Misbehaved {
+                participant,
+                reason,
+            }) => {
+                assert_eq!(participant, cheater);
+                assert_eq!(reason, Misbehavior::BadPrivateShare);
+            }
+            _ => panic!("expected the cheater to be blamed"),
+        }
+    }
 }
@@ -46,12 +46,12 @@
 //! Then, the parties need to generate a key pair so that they can sign messages:
 //!
 //! - The parties run a distributed key generation protocol to setup a new key pair,
-//! which can be used for many signatures.
+//!   which can be used for many signatures.
 //!
 //! When the parties want to sign using a given key:
 //!
 //! - Using their shares of a private key, the parties can create a *presignature*,
-//! before knowing the message to sign.
+//!   before knowing the message to sign.
 //! - Once they know this message, they can use the presignature to create a complete signature.
 //!
 //! It's important that presignatures and triples are **never** reused.
@@ -100,6 +100,8 @@
 //! | Curve | Feature |
 //! |-------|---------|
 //! |Secp256k1|`k256`|
+//! |P-256|`p256`|
+//! |P-384|`p384`|
 //!
 //! For supporting any message hash, the API requires the user to supply
 //! the hash of a message when signing as a scalar directly.
@@ -109,28 +111,56 @@
 //! The protocol and its implementation do have a few known disadvantages at the moment:
 //!
 //! - The protocol does require generating triples in advance, but these can be generated without knowledge of the private key.
-//! - The protocol does not attempt to provide identifiable aborts.
-//!
-//! We also don't really intend to add identifiable aborts to Cait-Sith itself.
+//! - The protocol only provides limited identifiable aborts.
+//!
+//! When a check fails because of a message from a specific participant, such as
+//! a bad proof, a bad commitment opening, or a bad private share, the error
+//! returned is [`ProtocolError::Misbehaved`](protocol::ProtocolError::Misbehaved),
+//! and [`culprit`](protocol::ProtocolError::culprit) names that participant.
+//! Some checks in presigning and signing only look at the sum of everyone's contributions.
+//! Presigning with [`presign_with_blame`] instead commits to each participant's shares,
+//! so that a failure in these checks can also be attributed to a participant.
+//! We also don't really intend to provide full identifiable aborts in Cait-Sith itself.
 //! While these can be desirable in certain situations, we aren't satisfied
 //! with the way the property of identifiable aborts is modeled currently,
 //! and are working on improvements to this model.
 mod compat;
 mod constants;
 mod crypto;
+mod derive;
+#[cfg(any(feature = "key-export", test))]
+mod export;
 mod keyshare;
 mod math;
 mod participants;
 mod presign;
 mod proofs;
 pub mod protocol;
+mod repair;
+#[cfg(any(feature = "k256", test))]
+mod schnorr;
 mod serde;
 mod sign;
 #[cfg(test)]
 mod test;
+#[cfg(any(feature = "testing", test))]
+pub mod testing;
 pub mod triples;
 
 pub use compat::CSCurve;
-pub use keyshare::{keygen, refresh, reshare, KeygenOutput};
-pub use presign::{presign, PresignArguments, PresignOutput};
-pub use sign::{sign, FullSignature};
+pub use derive::{derive_key, ChainCode, DerivationError, DerivedKey, HARDENED_OFFSET};
+#[cfg(any(feature = "key-export", test))]
+pub use export::export_key;
+pub use keyshare::{
+    import_key, keygen, keygen_many, refresh, reshare, split_key, KeyCommitment, KeygenOutput,
+};
+pub use presign::{
+    presign, presign_with_blame, PresignArguments, PresignCommitments, PresignOutput,
+    PresignShareCommitment,
+};
+pub use repair::repair_share;
+#[cfg(feature = "k256")]
+pub use schnorr::{schnorr_verifying_key, sign_schnorr, TaprootTweak};
+#[cfg(feature = "k256")]
+pub use sign::EthereumSignature;
+pub use sign::{sign, sign_rerandomized, FullSignature};
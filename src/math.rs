@@ -3,6 +3,7 @@ use std::ops::{Add, AddAssign, Index, Mul, MulAssign};
 use elliptic_curve::{Field, Group};
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{
     compat::CSCurve,
@@ -10,7 +11,9 @@ use crate::{
 };
 
 /// Represents a polynomial with coefficients in the scalar field of the curve.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// The coefficients are wiped from memory when the polynomial is dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
 pub struct Polynomial<C: CSCurve> {
     /// The coefficients of our polynomial, from 0..size-1.
     coefficients: Vec<C::Scalar>,
@@ -69,7 +72,7 @@ impl<C: CSCurve> Polynomial<C> {
     ///
     /// This is much more efficient than evaluating at other points.
     pub fn evaluate_zero(&self) -> C::Scalar {
-        self.coefficients.get(0).cloned().unwrap_or_default()
+        self.coefficients.first().cloned().unwrap_or_default()
     }
 
     /// Set the zero value of this polynomial to a new scalar
@@ -143,7 +146,7 @@ impl<C: CSCurve> MulAssign<&C::Scalar> for Polynomial<C> {
 }
 
 /// A polynomial with group coefficients.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct GroupPolynomial<C: CSCurve> {
     #[serde(
         serialize_with = "serialize_projective_points::<C, _>",
@@ -176,7 +179,7 @@ impl<C: CSCurve> GroupPolynomial<C> {
     ///
     /// This is more efficient than evaluating at an arbitrary point.
     pub fn evaluate_zero(&self) -> C::ProjectivePoint {
-        self.coefficients.get(0).cloned().unwrap_or_default()
+        self.coefficients.first().cloned().unwrap_or_default()
     }
 
     /// Evaluate this polynomial at a specific value.
@@ -8,6 +8,7 @@ use std::{collections::HashMap, mem, ops::Index};
 
 use elliptic_curve::Field;
 use serde::Serialize;
+use zeroize::Zeroize;
 
 use crate::{compat::CSCurve, protocol::Participant};
 
@@ -72,6 +73,28 @@ impl ParticipantList {
         self.indices[&participant]
     }
 
+    /// Get the lagrange coefficient for a participant, evaluated at another participant.
+    ///
+    /// This lets us compute the share of that participant from the shares of this list,
+    /// in the same way that [Self::lagrange] lets us compute the secret.
+    pub fn lagrange_at<C: CSCurve>(&self, p: Participant, at: Participant) -> C::Scalar {
+        let p_scalar = p.scalar::<C>();
+        let at_scalar = at.scalar::<C>();
+
+        let mut top = C::Scalar::ONE;
+        let mut bot = C::Scalar::ONE;
+        for q in &self.participants {
+            if p == *q {
+                continue;
+            }
+            let q_scalar = q.scalar::<C>();
+            top *= q_scalar - at_scalar;
+            bot *= q_scalar - p_scalar;
+        }
+
+        top * bot.invert().unwrap()
+    }
+
     /// Get the lagrange coefficient for a participant, relative to this list.
     pub fn lagrange<C: CSCurve>(&self, p: Participant) -> C::Scalar {
         let p_scalar = p.scalar::<C>();
@@ -145,6 +168,16 @@ impl<'a, T> ParticipantMap<'a, T> {
         self.count == self.data.len()
     }
 
+    /// Iterate over the participants which haven't put anything in yet.
+    pub fn missing(&self) -> impl Iterator<Item = Participant> + '_ {
+        self.participants
+            .participants
+            .iter()
+            .zip(&self.data)
+            .filter(|(_, x)| x.is_none())
+            .map(|(p, _)| *p)
+    }
+
     /// Place the data for a participant in this map.
     ///
     /// This will do nothing if the participant is unknown, or already has a value
@@ -172,6 +205,15 @@ impl<'a, T> Index<Participant> for ParticipantMap<'a, T> {
     }
 }
 
+impl<'a, T: Zeroize> Zeroize for ParticipantMap<'a, T> {
+    fn zeroize(&mut self) {
+        // Only the values are wiped, so the map keeps track of who put something in.
+        for x in self.data.iter_mut().flatten() {
+            x.zeroize();
+        }
+    }
+}
+
 /// A way to count participants.
 ///
 /// This is used when you want to process a message from each participant only once.
@@ -230,4 +272,14 @@ impl<'a> ParticipantCounter<'a> {
     pub fn full(&self) -> bool {
         self.counter == 0
     }
+
+    /// Iterate over the participants which haven't been put in this counter yet.
+    pub fn missing(&self) -> impl Iterator<Item = Participant> + '_ {
+        self.participants
+            .participants
+            .iter()
+            .zip(&self.seen)
+            .filter(|(_, seen)| !**seen)
+            .map(|(p, _)| *p)
+    }
 }
@@ -1,28 +1,96 @@
+use std::iter;
+
 use elliptic_curve::{Field, Group, ScalarPrimitive};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::compat::CSCurve;
-use crate::participants::ParticipantCounter;
+use crate::compat::{CSCurve, SerializablePoint};
+use crate::participants::{ParticipantCounter, ParticipantMap};
 use crate::protocol::internal::{make_protocol, Context, SharedChannel};
-use crate::protocol::{InitializationError, Protocol};
+use crate::protocol::{InitializationError, Protocol, ProtocolId};
+use crate::serde::{
+    deserialize_affine_point, deserialize_scalar, serialize_affine_point, serialize_scalar,
+};
 use crate::triples::{TriplePub, TripleShare};
 use crate::KeygenOutput;
 use crate::{
     participants::ParticipantList,
-    protocol::{Participant, ProtocolError},
+    protocol::{Misbehavior, Participant, ProtocolError},
 };
 
+/// A participant's share of the presignature, committed to with the generator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresignShareCommitment<C: CSCurve> {
+    /// The participant holding this share.
+    pub participant: Participant,
+    /// Their share of the nonce value, times the generator.
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
+    pub big_k: C::AffinePoint,
+    /// Their share of the sigma value, times the generator.
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
+    pub big_sigma: C::AffinePoint,
+}
+
+/// Commitments to each participant's share of a presignature.
+///
+/// These are produced by [presign_with_blame], and allow signing to figure out
+/// which participant sent a bad share of the signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "C: CSCurve")]
+pub struct PresignCommitments<C: CSCurve> {
+    /// The nonce value, times the generator.
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
+    pub big_k: C::AffinePoint,
+    /// The sigma value, times the generator.
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
+    pub big_sigma: C::AffinePoint,
+    /// The commitment to each participant's share.
+    pub shares: Vec<PresignShareCommitment<C>>,
+}
+
 /// The output of the presigning protocol.
 ///
 /// This output is basically all the parts of the signature that we can perform
 /// without knowing the message.
-#[derive(Debug, Clone)]
+///
+/// The secret shares are wiped from memory when this is dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(bound = "C: CSCurve")]
 pub struct PresignOutput<C: CSCurve> {
     /// The public nonce commitment.
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
+    #[zeroize(skip)]
     pub big_r: C::AffinePoint,
     /// Our share of the nonce value.
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub k: C::Scalar,
     /// Our share of the sigma value.
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub sigma: C::Scalar,
+    /// Commitments to everyone's shares, if this was created by [presign_with_blame].
+    #[zeroize(skip)]
+    pub commitments: Option<PresignCommitments<C>>,
 }
 
 /// The arguments needed to create a presignature.
@@ -38,11 +106,24 @@ pub struct PresignArguments<C: CSCurve> {
     pub threshold: usize,
 }
 
+/// The commitments to their shares each participant sends, when blame is enabled.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(bound = "C: CSCurve")]
+struct ShareCommitments<C: CSCurve> {
+    k: SerializablePoint<C>,
+    kd: SerializablePoint<C>,
+    a: SerializablePoint<C>,
+    b: SerializablePoint<C>,
+    c: SerializablePoint<C>,
+    x: SerializablePoint<C>,
+}
+
 async fn do_presign<C: CSCurve>(
     mut chan: SharedChannel,
     participants: ParticipantList,
     me: Participant,
     args: PresignArguments<C>,
+    blame: bool,
 ) -> Result<PresignOutput<C>, ProtocolError> {
     // Spec 1.2 + 1.3
     let big_k: C::ProjectivePoint = args.triple0.1.big_a.into();
@@ -53,20 +134,17 @@ async fn do_presign<C: CSCurve>(
 
     let big_a: C::ProjectivePoint = args.triple1.1.big_a.into();
     let big_b: C::ProjectivePoint = args.triple1.1.big_b.into();
+    let big_c: C::ProjectivePoint = args.triple1.1.big_c.into();
 
     let lambda = participants.lagrange::<C>(me);
 
-    let k_i = args.triple0.0.a;
-    let k_prime_i = lambda * k_i;
+    let mut k_prime_i = lambda * args.triple0.0.a;
     let kd_i: C::Scalar = lambda * args.triple0.0.c;
 
-    let a_i = args.triple1.0.a;
-    let b_i = args.triple1.0.b;
-    let c_i = args.triple1.0.c;
-    let a_prime_i = lambda * a_i;
-    let b_prime_i = lambda * b_i;
+    let mut a_prime_i = lambda * args.triple1.0.a;
+    let mut b_prime_i = lambda * args.triple1.0.b;
 
-    let x_prime_i = lambda * args.keygen_out.private_share;
+    let mut x_prime_i = lambda * args.keygen_out.private_share;
 
     // Spec 1.4
     let wait0 = chan.next_waitpoint();
@@ -78,6 +156,11 @@ async fn do_presign<C: CSCurve>(
     // Spec 1.9
     let ka_i: C::Scalar = k_prime_i + a_prime_i;
     let xb_i: C::Scalar = x_prime_i + b_prime_i;
+    // The linearized shares aren't needed anymore, only their masked sums.
+    k_prime_i.zeroize();
+    a_prime_i.zeroize();
+    b_prime_i.zeroize();
+    x_prime_i.zeroize();
 
     // Spec 1.10
     let wait1 = chan.next_waitpoint();
@@ -87,20 +170,103 @@ async fn do_presign<C: CSCurve>(
         chan.send_many(wait1, &(ka_i, xb_i)).await;
     }
 
+    // Spec B.1
+    let wait_blame = chan.next_waitpoint();
+    let mut all_commitments = ParticipantMap::new(&participants);
+    if blame {
+        let g = C::ProjectivePoint::generator();
+        let my_commitments = ShareCommitments::<C> {
+            k: SerializablePoint::from_projective(&(g * args.triple0.0.a)),
+            kd: SerializablePoint::from_projective(&(g * args.triple0.0.c)),
+            a: SerializablePoint::from_projective(&(g * args.triple1.0.a)),
+            b: SerializablePoint::from_projective(&(g * args.triple1.0.b)),
+            c: SerializablePoint::from_projective(&(g * args.triple1.0.c)),
+            x: SerializablePoint::from_projective(&(g * args.keygen_out.private_share)),
+        };
+        chan.send_many(wait_blame, &my_commitments).await;
+        all_commitments.put(me, my_commitments);
+    }
+
+    // Spec B.2
+    let lambdas: Vec<(Participant, C::Scalar)> = participants
+        .others(me)
+        .chain(iter::once(me))
+        .map(|p| (p, participants.lagrange::<C>(p)))
+        .collect();
+    if blame {
+        while !all_commitments.full() {
+            let (from, commitments) = chan.recv(wait_blame, all_commitments.missing()).await?;
+            all_commitments.put(from, commitments);
+        }
+
+        // The key commitment fixes everyone's share of the private key, so a bad
+        // commitment to it can be blamed on the participant who sent it.
+        for (p, _) in &lambdas {
+            if *p == me {
+                continue;
+            }
+            let big_x_p: C::ProjectivePoint = args.keygen_out.commitment.public_share(*p).into();
+            if all_commitments[*p].x.to_projective() != big_x_p {
+                return Err(ProtocolError::Misbehaved {
+                    participant: *p,
+                    reason: Misbehavior::BadPublicShare,
+                });
+            }
+        }
+
+        // The triples only commit to the sum of the shares, so a mismatch here
+        // can't be tied to a single participant.
+        let combine = |f: fn(&ShareCommitments<C>) -> SerializablePoint<C>| -> C::ProjectivePoint {
+            lambdas
+                .iter()
+                .map(|(p, lambda_p)| f(&all_commitments[*p]).to_projective() * lambda_p)
+                .sum()
+        };
+        if combine(|c| c.k) != big_k
+            || combine(|c| c.kd) != big_kd.into()
+            || combine(|c| c.a) != big_a
+            || combine(|c| c.b) != big_b
+            || combine(|c| c.c) != big_c
+            || combine(|c| c.x) != big_x
+        {
+            return Err(ProtocolError::AssertionFailed(
+                "commitments to shares don't match the public values".to_string(),
+            ));
+        }
+    }
+
     // Spec 2.1 and 2.2
     let mut kd = kd_i;
+    let mut all_kd = ParticipantMap::new(&participants);
     let mut seen = ParticipantCounter::new(&participants);
     seen.put(me);
     while !seen.full() {
-        let (from, kd_j): (_, ScalarPrimitive<C>) = chan.recv(wait0).await?;
+        let (from, kd_j): (_, ScalarPrimitive<C>) = chan.recv(wait0, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
         kd += C::Scalar::from(kd_j);
+        all_kd.put(from, C::Scalar::from(kd_j));
     }
 
     // Spec 2.3
     if big_kd != (C::ProjectivePoint::generator() * kd).into() {
+        // Spec B.3
+        if blame {
+            for (p, lambda_p) in &lambdas {
+                if *p == me {
+                    continue;
+                }
+                if C::ProjectivePoint::generator() * all_kd[*p]
+                    != all_commitments[*p].kd.to_projective() * lambda_p
+                {
+                    return Err(ProtocolError::Misbehaved {
+                        participant: *p,
+                        reason: Misbehavior::BadPublicShare,
+                    });
+                }
+            }
+        }
         return Err(ProtocolError::AssertionFailed(
             "received incorrect shares of kd".to_string(),
         ));
@@ -109,22 +275,45 @@ async fn do_presign<C: CSCurve>(
     // Spec 2.4 and 2.5
     let mut ka = ka_i;
     let mut xb = xb_i;
+    let mut all_ka_xb = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
         let (from, (ka_j, xb_j)): (_, (ScalarPrimitive<C>, ScalarPrimitive<C>)) =
-            chan.recv(wait1).await?;
+            chan.recv(wait1, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
         ka += C::Scalar::from(ka_j);
         xb += C::Scalar::from(xb_j);
+        all_ka_xb.put(from, (C::Scalar::from(ka_j), C::Scalar::from(xb_j)));
     }
 
     // Spec 2.6
     if (C::ProjectivePoint::generator() * ka != big_k + big_a)
         || (C::ProjectivePoint::generator() * xb != big_x + big_b)
     {
+        // Spec B.4
+        if blame {
+            for (p, lambda_p) in &lambdas {
+                if *p == me {
+                    continue;
+                }
+                let (ka_j, xb_j) = all_ka_xb[*p];
+                let commitments = &all_commitments[*p];
+                if C::ProjectivePoint::generator() * ka_j
+                    != (commitments.k.to_projective() + commitments.a.to_projective()) * lambda_p
+                    || C::ProjectivePoint::generator() * xb_j
+                        != (commitments.x.to_projective() + commitments.b.to_projective())
+                            * lambda_p
+                {
+                    return Err(ProtocolError::Misbehaved {
+                        participant: *p,
+                        reason: Misbehavior::BadPublicShare,
+                    });
+                }
+            }
+        }
         return Err(ProtocolError::AssertionFailed(
             "received incorrect shares of additive triple phase.".to_string(),
         ));
@@ -137,12 +326,39 @@ async fn do_presign<C: CSCurve>(
     let big_r = (C::ProjectivePoint::from(big_d) * kd_inv).into();
 
     // Spec 2.8
-    let sigma_i = ka * args.keygen_out.private_share - xb * a_i + c_i;
+    // We read the shares directly from the arguments, which get wiped when dropped.
+    let sigma_i = ka * args.keygen_out.private_share - xb * args.triple1.0.a + args.triple1.0.c;
+
+    // Spec B.5
+    let commitments = if blame {
+        let shares = lambdas
+            .iter()
+            .map(|(p, _)| {
+                let commitments = &all_commitments[*p];
+                let big_sigma_p = commitments.x.to_projective() * ka
+                    - commitments.a.to_projective() * xb
+                    + commitments.c.to_projective();
+                PresignShareCommitment {
+                    participant: *p,
+                    big_k: commitments.k.to_projective().into(),
+                    big_sigma: big_sigma_p.into(),
+                }
+            })
+            .collect();
+        Some(PresignCommitments {
+            big_k: big_k.into(),
+            big_sigma: (big_x * ka - big_a * xb + big_c).into(),
+            shares,
+        })
+    } else {
+        None
+    };
 
     Ok(PresignOutput {
         big_r,
-        k: k_i,
+        k: args.triple0.0.a,
         sigma: sigma_i,
+        commitments,
     })
 }
 
@@ -153,10 +369,43 @@ async fn do_presign<C: CSCurve>(
 ///
 /// This work does depend on the private key though, and it's crucial
 /// that a presignature is never used.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
 pub fn presign<C: CSCurve>(
     participants: &[Participant],
     me: Participant,
     args: PresignArguments<C>,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    presign_internal(participants, me, args, session_id, false)
+}
+
+/// Like [presign], except that participants also commit to their shares.
+///
+/// This costs an extra broadcast of a few points per participant, but allows
+/// the protocol to identify which participant sent a bad share if a check fails,
+/// rather than just aborting. The output also contains commitments to each
+/// participant's share of the presignature, which [crate::sign] will use to
+/// identify a participant sending a bad signature share.
+///
+/// All participants must agree on whether or not to use this variant,
+/// and mixing it with [presign] fails with [ProtocolError::ProtocolMismatch].
+pub fn presign_with_blame<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    args: PresignArguments<C>,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    presign_internal(participants, me, args, session_id, true)
+}
+
+fn presign_internal<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    args: PresignArguments<C>,
+    session_id: &[u8],
+    blame: bool,
 ) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
@@ -185,8 +434,13 @@ pub fn presign<C: CSCurve>(
         InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
     })?;
 
-    let ctx = Context::new();
-    let fut = do_presign(ctx.shared_channel(), participants, me, args);
+    let id = if blame {
+        ProtocolId::PresignWithBlame
+    } else {
+        ProtocolId::Presign
+    };
+    let ctx = Context::new(id, session_id);
+    let fut = do_presign(ctx.shared_channel(), participants, me, args, blame);
     Ok(make_protocol(ctx, fut))
 }
 
@@ -195,9 +449,13 @@ mod test {
     use super::*;
     use rand_core::OsRng;
 
-    use crate::{math::Polynomial, protocol::run_protocol, triples};
+    use crate::{
+        math::Polynomial,
+        protocol::{internal::tamper_payload, run_protocol, Action, MessageData, Tamper},
+        triples, KeyCommitment,
+    };
 
-    use k256::{ProjectivePoint, Secp256k1};
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
 
     #[test]
     fn test_presign() {
@@ -226,8 +484,8 @@ mod test {
         for ((p, triple0), triple1) in participants
             .iter()
             .take(3)
-            .zip(triple0_shares.into_iter())
-            .zip(triple1_shares.into_iter())
+            .zip(triple0_shares)
+            .zip(triple1_shares)
         {
             let protocol = presign(
                 &participants[..3],
@@ -238,9 +496,11 @@ mod test {
                     keygen_out: KeygenOutput {
                         private_share: f.evaluate(&p.scalar::<Secp256k1>()),
                         public_key: big_x,
+                        commitment: KeyCommitment::from_polynomial(&f),
                     },
                     threshold,
                 },
+                b"test presign",
             );
             assert!(protocol.is_ok());
             let protocol = protocol.unwrap();
@@ -258,8 +518,8 @@ mod test {
         let big_k = result[2].1.big_r;
 
         let participants = vec![result[0].0, result[1].0];
-        let k_shares = vec![result[0].1.k, result[1].1.k];
-        let sigma_shares = vec![result[0].1.sigma, result[1].1.sigma];
+        let k_shares = [result[0].1.k, result[1].1.k];
+        let sigma_shares = [result[0].1.sigma, result[1].1.sigma];
         let p_list = ParticipantList::new(&participants).unwrap();
         let k = p_list.lagrange::<Secp256k1>(participants[0]) * k_shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * k_shares[1];
@@ -268,4 +528,167 @@ mod test {
             + p_list.lagrange::<Secp256k1>(participants[1]) * sigma_shares[1];
         assert_eq!(sigma, k * f.evaluate_zero());
     }
+
+    #[test]
+    fn test_presign_with_blame() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, threshold);
+
+        // Tamper with the nth broadcast of one participant, if any.
+        #[allow(clippy::type_complexity)]
+        let make_protocols = |bad: Option<(Participant, usize, fn(&mut MessageData))>| {
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+            )> = Vec::with_capacity(participants.len());
+            for ((p, triple0), triple1) in participants
+                .iter()
+                .zip(triple0_shares.iter())
+                .zip(triple1_shares.iter())
+            {
+                let protocol = presign_with_blame(
+                    &participants,
+                    *p,
+                    PresignArguments {
+                        triple0: (triple0.clone(), triple0_pub.clone()),
+                        triple1: (triple1.clone(), triple1_pub.clone()),
+                        keygen_out: KeygenOutput {
+                            private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                            public_key: big_x,
+                            commitment: KeyCommitment::from_polynomial(&f),
+                        },
+                        threshold,
+                    },
+                    b"test presign with blame",
+                )
+                .unwrap();
+                let p = *p;
+                let mut sent = 0;
+                let tamper = move |action: &mut Action<_>| {
+                    if let Action::SendMany(m) = action {
+                        sent += 1;
+                        match bad {
+                            Some((bad, n, f)) if bad == p && n == sent => f(m),
+                            _ => {}
+                        }
+                    }
+                };
+                protocols.push((
+                    p,
+                    Box::new(Tamper { protocol, tamper }) as Box<dyn Protocol<Output = _>>,
+                ));
+            }
+            protocols
+        };
+
+        let result = run_protocol(make_protocols(None)).unwrap();
+        for (_, out) in &result {
+            let commitments = out.commitments.as_ref().unwrap();
+            assert_eq!(commitments.big_k, triple0_pub.big_a);
+            assert_eq!(commitments.shares.len(), participants.len());
+        }
+
+        let bad = participants[1];
+        fn bump(point: SerializablePoint<Secp256k1>) -> SerializablePoint<Secp256k1> {
+            SerializablePoint::from_projective(
+                &(point.to_projective() + ProjectivePoint::GENERATOR),
+            )
+        }
+
+        // The first broadcast contains the share of kd.
+        let bad_kd: fn(&mut MessageData) = |m| {
+            tamper_payload(m, |kd_i: ScalarPrimitive<Secp256k1>| {
+                (Scalar::from(kd_i) + Scalar::ONE).into()
+            })
+        };
+        match run_protocol(make_protocols(Some((bad, 1, bad_kd)))) {
+            Err(ProtocolError::Misbehaved {
+                participant,
+                reason: Misbehavior::BadPublicShare,
+            }) => assert_eq!(participant, bad),
+            other => panic!("expected a bad public share, got {:?}", other.err()),
+        }
+
+        // The third broadcast contains the commitments to the shares.
+        let bad_x: fn(&mut MessageData) = |m| {
+            tamper_payload(m, |mut c: ShareCommitments<Secp256k1>| {
+                c.x = bump(c.x);
+                c
+            })
+        };
+        match run_protocol(make_protocols(Some((bad, 3, bad_x)))) {
+            Err(ProtocolError::Misbehaved {
+                participant,
+                reason: Misbehavior::BadPublicShare,
+            }) => assert_eq!(participant, bad),
+            other => panic!("expected a bad public share, got {:?}", other.err()),
+        }
+
+        // Only the sum of the shares of a triple is known, so this can't be blamed.
+        let bad_a: fn(&mut MessageData) = |m| {
+            tamper_payload(m, |mut c: ShareCommitments<Secp256k1>| {
+                c.a = bump(c.a);
+                c
+            })
+        };
+        assert!(matches!(
+            run_protocol(make_protocols(Some((bad, 3, bad_a)))),
+            Err(ProtocolError::AssertionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_presign_with_blame_mismatch() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, threshold);
+
+        // Only the first participant enables blame.
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for ((p, triple0), triple1) in participants.iter().zip(triple0_shares).zip(triple1_shares) {
+            let args = PresignArguments {
+                triple0: (triple0, triple0_pub.clone()),
+                triple1: (triple1, triple1_pub.clone()),
+                keygen_out: KeygenOutput {
+                    private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                    public_key: big_x,
+                    commitment: KeyCommitment::from_polynomial(&f),
+                },
+                threshold,
+            };
+            let session_id = b"test presign with blame mismatch";
+            if *p == participants[0] {
+                let protocol = presign_with_blame(&participants, *p, args, session_id).unwrap();
+                protocols.push((*p, Box::new(protocol)));
+            } else {
+                let protocol = presign(&participants, *p, args, session_id).unwrap();
+                protocols.push((*p, Box::new(protocol)));
+            }
+        }
+
+        assert!(matches!(
+            run_protocol(protocols),
+            Err(ProtocolError::ProtocolMismatch {
+                ours: ProtocolId::Presign | ProtocolId::PresignWithBlame,
+                ..
+            })
+        ));
+    }
 }
@@ -43,19 +43,32 @@
 //! are deterministic, even in the presence of concurrent tasks.
 use ck_meow::Meow;
 use event_listener::Event;
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng, RngCore};
 use serde::{de::DeserializeOwned, Serialize};
 use smol::{
     block_on,
     channel::{self, Receiver, Sender},
     future,
     lock::Mutex,
-    Executor, Task,
+    ready, Executor, Task,
 };
-use std::{collections::HashMap, error, future::Future, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::Arc,
+    task::{self, Poll},
+};
+use zeroize::Zeroizing;
 
-use crate::serde::{decode, encode_with_tag};
+use crate::serde::Codec;
 
-use super::{Action, MessageData, Participant, Protocol, ProtocolError};
+use super::{
+    Action, AsyncProtocol, Drive, InitializationError, MessageData, Misbehavior, Participant,
+    Protocol, ProtocolError, ProtocolId, Snapshot, Waiting, WaitpointId, WIRE_VERSION,
+};
 
 /// The domain for our use of meow here.
 const MEOW_DOMAIN: &[u8] = b"cait-sith channel tags";
@@ -69,26 +82,32 @@ impl ChannelTag {
     const SIZE: usize = 20;
     /// The channel tag for a shared channel.
     ///
-    /// This will always yield the same tag, and is intended to be the root for shared channels.
-    fn root_shared() -> Self {
+    /// This will always yield the same tag for a given session, and is intended
+    /// to be the root for shared channels.
+    fn root_shared(session_id: &[u8]) -> Self {
         let mut out = [0u8; Self::SIZE];
         let mut meow = Meow::new(MEOW_DOMAIN);
         meow.meta_ad(b"root shared", false);
+        meow.meta_ad(b"session id", false);
+        meow.ad(session_id, false);
         meow.prf(&mut out, false);
         Self(out)
     }
 
     /// The channel tag for a private channel.
     ///
-    /// This will always yield the same tag, and is intended to be the root for private channels.
+    /// This will always yield the same tag for a given session, and is intended
+    /// to be the root for private channels.
     ///
     /// This tag will depend on the set of participants used; the order they're passed into this
     /// function does not matter.
-    fn root_private(p0: Participant, p1: Participant) -> Self {
+    fn root_private(session_id: &[u8], p0: Participant, p1: Participant) -> Self {
         // Sort participants, for uniqueness.
         let (p0, p1) = (p0.min(p1), p0.max(p1));
         let mut meow = Meow::new(MEOW_DOMAIN);
         meow.meta_ad(b"root private", false);
+        meow.meta_ad(b"session id", false);
+        meow.ad(session_id, false);
         meow.meta_ad(b"p0", false);
         meow.ad(&p0.bytes(), false);
         meow.meta_ad(b"p1", false);
@@ -183,6 +202,101 @@ impl MessageHeader {
     }
 }
 
+/// The reasons an envelope can fail to open.
+#[derive(Debug)]
+enum EnvelopeError {
+    /// The message was too short, or its length didn't match the payload.
+    Malformed,
+    /// The message was for a different version of the wire format.
+    Version(u16),
+    /// The message was for a different protocol.
+    Protocol(u8),
+    /// The message was encoded with a different codec.
+    Codec(u8),
+}
+
+/// The envelope wrapping every message.
+///
+/// This starts with the wire version, followed by the protocol id, the codec,
+/// the header routing the message, and then the length of the payload which follows.
+/// Only the position of the wire version is fixed across versions, everything
+/// else can change along with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Envelope {
+    protocol: ProtocolId,
+    codec: Codec,
+    header: MessageHeader,
+}
+
+impl Envelope {
+    /// The number of bytes before the payload.
+    const LEN: usize = 2 + 1 + 1 + MessageHeader::LEN + 4;
+
+    /// Wrap the encoding of some data with this envelope.
+    fn seal<T: Serialize>(&self, data: &T) -> MessageData {
+        let mut prefix = [0u8; Self::LEN];
+        prefix[..2].copy_from_slice(&WIRE_VERSION.to_le_bytes());
+        prefix[2] = self.protocol as u8;
+        prefix[3] = self.codec as u8;
+        prefix[4..4 + MessageHeader::LEN].copy_from_slice(&self.header.to_bytes());
+        let mut out = self.codec.encode_with_tag(&prefix, data);
+        let len = u32::try_from(out.len() - Self::LEN).expect("message is too large");
+        out[Self::LEN - 4..Self::LEN].copy_from_slice(&len.to_le_bytes());
+        out
+    }
+
+    /// Open a message, expecting it to be for a given protocol, and encoded with a given codec.
+    ///
+    /// This returns the header of the message, and its payload.
+    fn open(
+        protocol: ProtocolId,
+        codec: Codec,
+        data: &[u8],
+    ) -> Result<(MessageHeader, &[u8]), EnvelopeError> {
+        if data.len() < 2 {
+            return Err(EnvelopeError::Malformed);
+        }
+        // Unwrapping is fine because we checked the length already.
+        let version = u16::from_le_bytes(data[..2].try_into().unwrap());
+        if version != WIRE_VERSION {
+            return Err(EnvelopeError::Version(version));
+        }
+        if data.len() < Self::LEN {
+            return Err(EnvelopeError::Malformed);
+        }
+        if data[2] != protocol as u8 {
+            return Err(EnvelopeError::Protocol(data[2]));
+        }
+        if data[3] != codec as u8 {
+            return Err(EnvelopeError::Codec(data[3]));
+        }
+        let header = MessageHeader::from_bytes(&data[4..]).ok_or(EnvelopeError::Malformed)?;
+        let len = u32::from_le_bytes(data[Self::LEN - 4..Self::LEN].try_into().unwrap());
+        let payload = &data[Self::LEN..];
+        if usize::try_from(len).ok() != Some(payload.len()) {
+            return Err(EnvelopeError::Malformed);
+        }
+        Ok((header, payload))
+    }
+}
+
+/// Replace the payload of a message, encoded with MessagePack, keeping its envelope.
+///
+/// This is useful for testing that misbehavior gets caught.
+#[cfg(test)]
+pub(crate) fn tamper_payload<T: Serialize + DeserializeOwned>(
+    data: &mut MessageData,
+    f: impl FnOnce(T) -> T,
+) {
+    let payload = Codec::MessagePack
+        .decode(&data[Envelope::LEN..])
+        .expect("failed to decode payload");
+    let mut out = Codec::MessagePack.encode_with_tag(&data[..Envelope::LEN], &f(payload));
+    let len = u32::try_from(out.len() - Envelope::LEN).expect("message is too large");
+    out[Envelope::LEN - 4..Envelope::LEN].copy_from_slice(&len.to_le_bytes());
+    *data = out;
+}
+
 type SubMessageQueue = Vec<(Participant, MessageData)>;
 
 /// A message buffer is a concurrent data structure to buffer messages.
@@ -248,42 +362,369 @@ pub enum Message {
     Private(Participant, MessageData),
 }
 
+/// The waits registered with a [WaitingRegistry], keyed by a token for each wait.
+#[derive(Default)]
+struct WaitingState {
+    next_token: u64,
+    waits: HashMap<u64, (MessageHeader, Vec<Participant>)>,
+}
+
+/// Keeps track of which participants we're waiting on, at each header.
+///
+/// Several waits can be registered at the same header at once, each with their
+/// own guard.
+#[derive(Clone, Default)]
+struct WaitingRegistry(Arc<std::sync::Mutex<WaitingState>>);
+
+impl WaitingRegistry {
+    /// Register that we're waiting for messages at a header.
+    ///
+    /// This registration lasts until the returned guard is dropped.
+    fn wait(&self, header: MessageHeader, participants: Vec<Participant>) -> WaitingGuard {
+        let mut lock = self.0.lock().expect("waiting registry poisoned");
+        let token = lock.next_token;
+        lock.next_token += 1;
+        lock.waits.insert(token, (header, participants));
+        WaitingGuard {
+            registry: self.clone(),
+            token,
+        }
+    }
+
+    /// Return everything we're waiting on, sorted by waitpoint.
+    fn snapshot(&self) -> Vec<Waiting> {
+        let lock = self.0.lock().expect("waiting registry poisoned");
+        let mut by_header: HashMap<MessageHeader, Vec<Participant>> = HashMap::new();
+        for (header, participants) in lock.waits.values() {
+            by_header
+                .entry(*header)
+                .or_default()
+                .extend(participants.iter().copied());
+        }
+        let mut out: Vec<_> = by_header
+            .into_iter()
+            .filter(|(_, participants)| !participants.is_empty())
+            .map(|(header, mut participants)| {
+                participants.sort();
+                participants.dedup();
+                Waiting {
+                    waitpoint: WaitpointId(header.to_bytes()),
+                    participants,
+                }
+            })
+            .collect();
+        out.sort_by_key(|w| w.waitpoint);
+        out
+    }
+}
+
+/// Removes a registration once we're no longer waiting, even if the wait was cancelled.
+struct WaitingGuard {
+    registry: WaitingRegistry,
+    token: u64,
+}
+
+impl Drop for WaitingGuard {
+    fn drop(&mut self) {
+        if let Ok(mut lock) = self.registry.0.lock() {
+            lock.waits.remove(&self.token);
+        }
+    }
+}
+
+/// The most messages we'll accept from a single participant in a single protocol.
+///
+/// This is far more than any protocol needs, and stops a participant from
+/// making us hold an unbounded number of messages.
+const MAX_RECEIVED_PER_PARTICIPANT: usize = 1 << 16;
+
+/// The state recorded by a [Journal].
+#[derive(Default)]
+struct JournalState {
+    /// The secret seed all of the randomness of the protocol is derived from.
+    seed: Zeroizing<[u8; 32]>,
+    /// Whether or not some randomness has been derived from the seed.
+    seed_used: bool,
+    /// The codec used to encode and decode messages.
+    codec: Codec,
+    /// Every message we've received so far, in order.
+    received: Vec<(Participant, MessageData)>,
+    /// The headers of the messages we've received from each participant.
+    received_headers: HashMap<Participant, HashSet<MessageHeader>>,
+    /// Every message we've sent so far, in order.
+    sent: Vec<(Option<Participant>, MessageData)>,
+    /// The messages sent before the protocol was restored, which it will produce, and send, again.
+    resent: HashMap<(MessageHeader, Option<Participant>), VecDeque<MessageData>>,
+}
+
+impl JournalState {
+    /// Whether or not the protocol has done anything yet.
+    fn started(&self) -> bool {
+        self.seed_used || !self.received.is_empty() || !self.sent.is_empty()
+    }
+}
+
+/// Records what a protocol has done, allowing it to be snapshotted and restored.
+///
+/// All of the randomness of a protocol is derived from a secret seed, and the
+/// protocol is deterministic given this seed and the messages it receives.
+/// This means that we can restore a protocol by replaying the messages it received.
+/// The protocol will then produce the messages it sent once again, which we check
+/// against the ones recorded in the snapshot, so that restoring can never lead to
+/// a protocol sending two different messages for the same waitpoint.
+/// This only holds for messages in the snapshot, which is why a snapshot needs to
+/// be stored before each message is sent.
+#[derive(Clone)]
+struct Journal(Arc<std::sync::Mutex<JournalState>>);
+
+impl Journal {
+    fn new() -> Self {
+        let mut state = JournalState::default();
+        OsRng.fill_bytes(state.seed.as_mut());
+        Self(Arc::new(std::sync::Mutex::new(state)))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, JournalState> {
+        self.0.lock().expect("journal poisoned")
+    }
+
+    /// Derive a new source of randomness, unique to a channel tag and label.
+    fn rng(&self, tag: &ChannelTag, label: &[u8]) -> MeowRng {
+        let mut state = self.lock();
+        state.seed_used = true;
+        let mut meow = Meow::new(MEOW_DOMAIN);
+        meow.meta_ad(b"rng seed", false);
+        meow.key(state.seed.as_ref(), false);
+        meow.meta_ad(b"channel", false);
+        meow.ad(&tag.0, false);
+        meow.meta_ad(b"label", false);
+        meow.ad(label, false);
+        let mut seed = Zeroizing::new([0u8; 32]);
+        meow.prf(seed.as_mut(), false);
+        MeowRng::new(&seed)
+    }
+
+    /// Record a message we've received, returning whether it should be delivered.
+    ///
+    /// Each participant sends at most one message at each header, so we ignore
+    /// any later message at the same header, as well as any messages past
+    /// [MAX_RECEIVED_PER_PARTICIPANT].
+    fn record_received(
+        &self,
+        from: Participant,
+        header: MessageHeader,
+        data: &MessageData,
+    ) -> bool {
+        let mut state = self.lock();
+        let headers = state.received_headers.entry(from).or_default();
+        if headers.len() >= MAX_RECEIVED_PER_PARTICIPANT || !headers.insert(header) {
+            return false;
+        }
+        state.received.push((from, data.clone()));
+        true
+    }
+
+    /// Record a message we want to send.
+    ///
+    /// If the protocol was restored, and already sent a message at the same waitpoint,
+    /// this fails unless the message is the same. We send that message once again,
+    /// since we don't know if it was delivered before stopping, and other participants
+    /// ignore messages they've already received.
+    fn record_sent(&self, protocol: ProtocolId, message: &Message) -> Result<(), ProtocolError> {
+        let (to, data) = match message {
+            Message::Many(data) => (None, data),
+            Message::Private(to, data) => (Some(*to), data),
+        };
+        let mut state = self.lock();
+        // Our own messages always have a valid envelope.
+        let (header, _) =
+            Envelope::open(protocol, state.codec, data).expect("sent message without envelope");
+        let previous = state
+            .resent
+            .get_mut(&(header, to))
+            .and_then(|queue| queue.pop_front());
+        if matches!(previous, Some(previous) if &previous != data) {
+            return Err(ProtocolError::AssertionFailed(
+                "restored protocol tried to send a different message at the same waitpoint"
+                    .to_string(),
+            ));
+        }
+        state.sent.push((to, data.clone()));
+        Ok(())
+    }
+
+    fn snapshot(&self, session_id: &[u8]) -> Snapshot {
+        let state = self.lock();
+        let mut sent = state.sent.clone();
+        // Messages we haven't resent yet still count as having been sent.
+        for ((_, to), queue) in &state.resent {
+            sent.extend(queue.iter().map(|data| (*to, data.clone())));
+        }
+        Snapshot {
+            session_id: session_id.to_vec(),
+            seed: *state.seed,
+            codec: state.codec,
+            received: state.received.clone(),
+            sent,
+        }
+    }
+
+    fn codec(&self) -> Codec {
+        self.lock().codec
+    }
+
+    /// Change the codec used for messages, which can only happen before starting.
+    fn set_codec(&self, codec: Codec) -> Result<(), InitializationError> {
+        let mut state = self.lock();
+        if state.started() {
+            return Err(InitializationError::BadParameters(
+                "codec must be set before the protocol has been started".to_string(),
+            ));
+        }
+        state.codec = codec;
+        Ok(())
+    }
+
+    /// Replace the seed with one drawn from a given source of randomness.
+    fn seed_from(&self, rng: &mut dyn CryptoRngCore) -> Result<(), InitializationError> {
+        let mut state = self.lock();
+        if state.started() {
+            return Err(InitializationError::BadParameters(
+                "protocol must be seeded before it has been started".to_string(),
+            ));
+        }
+        rng.fill_bytes(state.seed.as_mut());
+        Ok(())
+    }
+
+    /// Restore the seed and sent messages of a snapshot.
+    ///
+    /// This returns the messages which were received, which need to be delivered again.
+    fn restore(
+        &self,
+        protocol: ProtocolId,
+        session_id: &[u8],
+        mut snapshot: Snapshot,
+    ) -> Result<Vec<(Participant, MessageData)>, InitializationError> {
+        if snapshot.session_id != session_id {
+            return Err(InitializationError::BadParameters(
+                "snapshot has a different session id".to_string(),
+            ));
+        }
+        let mut state = self.lock();
+        if state.started() {
+            return Err(InitializationError::BadParameters(
+                "protocol must be restored before it has been started".to_string(),
+            ));
+        }
+        *state.seed = snapshot.seed;
+        state.codec = snapshot.codec;
+        for (to, data) in mem::take(&mut snapshot.sent) {
+            let header = match Envelope::open(protocol, state.codec, &data) {
+                Ok((header, _)) => header,
+                Err(EnvelopeError::Version(_)) => {
+                    return Err(InitializationError::BadParameters(
+                        "snapshot has a different wire version".to_string(),
+                    ))
+                }
+                Err(_) => {
+                    return Err(InitializationError::BadParameters(
+                        "snapshot has a malformed message".to_string(),
+                    ))
+                }
+            };
+            state
+                .resent
+                .entry((header, to))
+                .or_default()
+                .push_back(data);
+        }
+        Ok(mem::take(&mut snapshot.received))
+    }
+}
+
 #[derive(Clone)]
 struct Comms {
+    protocol: ProtocolId,
     buffer: MessageBuffer,
     message_s: Sender<Message>,
     message_r: Receiver<Message>,
+    /// Errors caused by incoming messages, rather than by the protocol itself.
+    failure_s: Sender<ProtocolError>,
+    failure_r: Receiver<ProtocolError>,
+    waiting: WaitingRegistry,
+    journal: Journal,
 }
 
 impl Comms {
-    pub fn new() -> Self {
+    pub fn new(protocol: ProtocolId) -> Self {
         let (message_s, message_r) = channel::bounded(1);
+        let (failure_s, failure_r) = channel::unbounded();
 
         Self {
+            protocol,
             buffer: MessageBuffer::new(),
             message_s,
             message_r,
+            failure_s,
+            failure_r,
+            waiting: WaitingRegistry::default(),
+            journal: Journal::new(),
         }
     }
 
-    async fn outgoing(&self) -> Message {
-        self.message_r
+    /// Wait for the next message we need to send.
+    async fn outgoing(&self) -> Result<Message, ProtocolError> {
+        let message = self
+            .message_r
             .recv()
             .await
-            .expect("failed to check outgoing messages")
+            .expect("failed to check outgoing messages");
+        self.journal.record_sent(self.protocol, &message)?;
+        Ok(message)
     }
 
-    async fn push_message(&self, from: Participant, message: MessageData) {
-        if message.len() < MessageHeader::LEN {
-            return;
-        }
+    /// Wait for an incoming message to make the protocol fail.
+    async fn failure(&self) -> ProtocolError {
+        self.failure_r
+            .recv()
+            .await
+            .expect("failed to check for failures")
+    }
 
-        let header = match MessageHeader::from_bytes(&message) {
-            Some(h) => h,
-            _ => return,
+    async fn push_message(&self, from: Participant, message: MessageData) {
+        let header = match Envelope::open(self.protocol, self.journal.codec(), &message) {
+            Ok((header, _)) => header,
+            Err(EnvelopeError::Malformed) => return,
+            Err(EnvelopeError::Version(theirs)) => {
+                let _ = self.failure_s.try_send(ProtocolError::VersionMismatch {
+                    participant: from,
+                    ours: WIRE_VERSION,
+                    theirs,
+                });
+                return;
+            }
+            Err(EnvelopeError::Protocol(theirs)) => {
+                let _ = self.failure_s.try_send(ProtocolError::ProtocolMismatch {
+                    participant: from,
+                    ours: self.protocol,
+                    theirs,
+                });
+                return;
+            }
+            Err(EnvelopeError::Codec(theirs)) => {
+                let _ = self.failure_s.try_send(ProtocolError::CodecMismatch {
+                    participant: from,
+                    ours: self.journal.codec(),
+                    theirs,
+                });
+                return;
+            }
         };
 
-        self.buffer.push(header, from, message).await
+        if self.journal.record_received(from, header, &message) {
+            self.buffer.push(header, from, message).await
+        }
     }
 
     async fn send_raw(&self, data: Message) {
@@ -295,26 +736,41 @@ impl Comms {
 
     /// (Indicate that you want to) send a message to everybody else.
     async fn send_many<T: Serialize>(&self, header: MessageHeader, data: &T) {
-        let header_bytes = header.to_bytes();
-        let message_data = encode_with_tag(&header_bytes, data);
+        let message_data = self.envelope(header).seal(data);
         self.send_raw(Message::Many(message_data)).await;
     }
 
     /// (Indicate that you want to) send a message privately to someone.
     async fn send_private<T: Serialize>(&self, header: MessageHeader, to: Participant, data: &T) {
-        let header_bytes = header.to_bytes();
-        let message_data = encode_with_tag(&header_bytes, data);
+        let message_data = self.envelope(header).seal(data);
         self.send_raw(Message::Private(to, message_data)).await;
     }
 
-    async fn recv<T: DeserializeOwned>(
+    fn envelope(&self, header: MessageHeader) -> Envelope {
+        Envelope {
+            protocol: self.protocol,
+            codec: self.journal.codec(),
+            header,
+        }
+    }
+
+    /// Receive a message, noting which participants we expect it to come from while we wait.
+    async fn recv<T: Serialize + DeserializeOwned>(
         &self,
         header: MessageHeader,
+        waiting_on: Vec<Participant>,
     ) -> Result<(Participant, T), ProtocolError> {
-        let (from, data) = self.buffer.pop(header).await;
-        let decoded: Result<T, Box<dyn error::Error + Send + Sync>> =
-            decode(&data[MessageHeader::LEN..]).map_err(|e| e.into());
-        Ok((from, decoded?))
+        let (from, data) = {
+            let _guard = self.waiting.wait(header, waiting_on);
+            self.buffer.pop(header).await
+        };
+        let decoded = self.journal.codec().decode(&data[Envelope::LEN..]).ok_or(
+            ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::MalformedMessage,
+            },
+        )?;
+        Ok((from, decoded))
     }
 }
 
@@ -325,10 +781,10 @@ pub struct SharedChannel {
 }
 
 impl SharedChannel {
-    fn new(comms: Comms) -> Self {
+    fn new(comms: Comms, session_id: &[u8]) -> Self {
         Self {
             comms,
-            header: MessageHeader::new(ChannelTag::root_shared()),
+            header: MessageHeader::new(ChannelTag::root_shared(session_id)),
         }
     }
 
@@ -337,6 +793,15 @@ impl SharedChannel {
         self.header.next_waitpoint()
     }
 
+    /// Get a source of randomness for this channel.
+    ///
+    /// Each label will produce a different source of randomness. All of the
+    /// randomness a protocol uses needs to come from a function like this,
+    /// in order for the protocol to be restored from a snapshot.
+    pub fn rng(&self, label: &[u8]) -> MeowRng {
+        self.comms.journal.rng(&self.header.channel, label)
+    }
+
     pub async fn send_many<T: Serialize>(&self, waitpoint: Waitpoint, data: &T) {
         self.comms
             .send_many(self.header.with_waitpoint(waitpoint), data)
@@ -354,11 +819,21 @@ impl SharedChannel {
             .await
     }
 
-    pub async fn recv<T: DeserializeOwned>(
+    /// Receive a message sent on a given waitpoint.
+    ///
+    /// We also need the participants we're still expecting a message from,
+    /// which lets the protocol report who it's blocked on.
+    pub async fn recv<T: Serialize + DeserializeOwned>(
         &self,
         waitpoint: Waitpoint,
+        missing: impl IntoIterator<Item = Participant>,
     ) -> Result<(Participant, T), ProtocolError> {
-        self.comms.recv(self.header.with_waitpoint(waitpoint)).await
+        self.comms
+            .recv(
+                self.header.with_waitpoint(waitpoint),
+                missing.into_iter().collect(),
+            )
+            .await
     }
 }
 
@@ -372,11 +847,11 @@ pub struct PrivateChannel {
 }
 
 impl PrivateChannel {
-    fn new(comms: Comms, from: Participant, to: Participant) -> Self {
+    fn new(comms: Comms, session_id: &[u8], from: Participant, to: Participant) -> Self {
         Self {
             comms,
             to,
-            header: MessageHeader::new(ChannelTag::root_private(from, to)),
+            header: MessageHeader::new(ChannelTag::root_private(session_id, from, to)),
         }
     }
 
@@ -392,20 +867,27 @@ impl PrivateChannel {
         self.header.next_waitpoint()
     }
 
+    /// Get a source of randomness for this channel.
+    ///
+    /// See [SharedChannel::rng].
+    pub fn rng(&self, label: &[u8]) -> MeowRng {
+        self.comms.journal.rng(&self.header.channel, label)
+    }
+
     pub async fn send<T: Serialize>(&self, waitpoint: Waitpoint, data: &T) {
         self.comms
             .send_private(self.header.with_waitpoint(waitpoint), self.to, data)
             .await
     }
 
-    pub async fn recv<T: DeserializeOwned>(
+    pub async fn recv<T: Serialize + DeserializeOwned>(
         &self,
         waitpoint: Waitpoint,
     ) -> Result<T, ProtocolError> {
         loop {
             let (from, data) = self
                 .comms
-                .recv(self.header.with_waitpoint(waitpoint))
+                .recv(self.header.with_waitpoint(waitpoint), vec![self.to])
                 .await?;
             if from != self.to {
                 future::yield_now().await;
@@ -420,33 +902,52 @@ impl PrivateChannel {
 ///
 /// This allows us to spawn new tasks, and send and receive messages.
 ///
+/// Each context is bound to a session id, which separates the channels
+/// of this run of a protocol from those of any other run.
+///
 /// This context can safely be cloned.
 #[derive(Clone)]
 pub struct Context<'a> {
     comms: Comms,
     executor: Arc<Executor<'a>>,
+    session_id: Arc<[u8]>,
 }
 
 impl<'a> Context<'a> {
-    pub fn new() -> Self {
+    pub fn new(protocol: ProtocolId, session_id: &[u8]) -> Self {
         Self {
-            comms: Comms::new(),
+            comms: Comms::new(protocol),
             executor: Arc::new(Executor::new()),
+            session_id: session_id.into(),
         }
     }
 
+    /// Return the session id this context was created with.
+    pub fn session_id(&self) -> &[u8] {
+        &self.session_id
+    }
+
+    /// Get a source of randomness for this context.
+    ///
+    /// See [SharedChannel::rng].
+    pub fn rng(&self, label: &[u8]) -> MeowRng {
+        self.comms
+            .journal
+            .rng(&ChannelTag::root_shared(&self.session_id), label)
+    }
+
     /// Return *the* shared channel for this context.
     ///
     /// To get other channels, use the successor function.
     pub fn shared_channel(&self) -> SharedChannel {
-        SharedChannel::new(self.comms.clone())
+        SharedChannel::new(self.comms.clone(), &self.session_id)
     }
 
     /// Return *the* private channel for this context.
     ///
     /// To get other channels, use the successor function.
     pub fn private_channel(&self, from: Participant, to: Participant) -> PrivateChannel {
-        PrivateChannel::new(self.comms.clone(), from, to)
+        PrivateChannel::new(self.comms.clone(), &self.session_id, from, to)
     }
 
     /// Spawn a new task on the executor.
@@ -460,11 +961,17 @@ impl<'a> Context<'a> {
     }
 }
 
+/// The future used to produce the next action of an executor, when running asynchronously.
+type PendingAction<'a, T> =
+    Pin<Box<dyn Future<Output = Result<Action<T>, ProtocolError>> + Send + 'a>>;
+
 /// This struct will convert a future into a protocol.
 struct ProtocolExecutor<'a, T> {
     ctx: Context<'a>,
     ret_r: channel::Receiver<Result<T, ProtocolError>>,
     done: bool,
+    /// The action we're in the middle of producing, when running asynchronously.
+    pending: Option<PendingAction<'a, T>>,
 }
 
 impl<'a, T: Send + 'a> ProtocolExecutor<'a, T> {
@@ -487,11 +994,12 @@ impl<'a, T: Send + 'a> ProtocolExecutor<'a, T> {
             ctx,
             ret_r,
             done: false,
+            pending: None,
         }
     }
 }
 
-impl<'a, T> Protocol for ProtocolExecutor<'a, T> {
+impl<'a, T: Send + 'a> Protocol for ProtocolExecutor<'a, T> {
     type Output = T;
 
     fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
@@ -507,12 +1015,13 @@ impl<'a, T> Protocol for ProtocolExecutor<'a, T> {
             Ok::<_, ProtocolError>(Action::Return(out?))
         };
         let fut_outgoing = async {
-            let action: Action<Self::Output> = match self.ctx.comms.outgoing().await {
+            let action: Action<Self::Output> = match self.ctx.comms.outgoing().await? {
                 Message::Many(m) => Action::SendMany(m),
                 Message::Private(to, m) => Action::SendPrivate(to, m),
             };
             Ok::<_, ProtocolError>(action)
         };
+        let fut_failure = async { Err(self.ctx.comms.failure().await) };
         // This is a future which will keep ticking the executor until
         // all tasks are asleep, at which point it will indicate that nothing
         // is left to do, by returning `Action::Wait`.
@@ -526,10 +1035,10 @@ impl<'a, T> Protocol for ProtocolExecutor<'a, T> {
         };
         // The priority is first to send all outgoing messages before returning,
         // otherwise we might deadlock other people, by preventing them from receiving the output.
-        let action = block_on(
-            self.ctx
-                .run(future::or(fut_outgoing, future::or(fut_return, fut_wait))),
-        );
+        let action = block_on(self.ctx.run(future::or(
+            fut_outgoing,
+            future::or(fut_failure, future::or(fut_return, fut_wait)),
+        )));
         match action {
             Err(_) => self.done = true,
             Ok(Action::Return(_)) => self.done = true,
@@ -545,6 +1054,103 @@ impl<'a, T> Protocol for ProtocolExecutor<'a, T> {
                 .run(self.ctx.comms.push_message(from, data)),
         );
     }
+
+    fn waiting_on(&self) -> Vec<Waiting> {
+        self.ctx.comms.waiting.snapshot()
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        Some(self.ctx.comms.journal.snapshot(&self.ctx.session_id))
+    }
+
+    fn seed_from_rng(&mut self, rng: &mut dyn CryptoRngCore) -> Result<(), InitializationError> {
+        self.ctx.comms.journal.seed_from(rng)
+    }
+
+    fn set_codec(&mut self, codec: Codec) -> Result<(), InitializationError> {
+        self.ctx.comms.journal.set_codec(codec)
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), InitializationError> {
+        let received = self.ctx.comms.journal.restore(
+            self.ctx.comms.protocol,
+            &self.ctx.session_id,
+            snapshot,
+        )?;
+        for (from, data) in received {
+            self.message(from, data);
+        }
+        Ok(())
+    }
+
+    fn into_async<'b>(self) -> AsyncProtocol<'b, T>
+    where
+        Self: Sized + Send + 'b,
+        T: Send + 'b,
+    {
+        AsyncProtocol::new(self)
+    }
+}
+
+impl<'a, T: Send + 'a> Drive<T> for ProtocolExecutor<'a, T> {
+    fn poll_action(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<Action<T>, ProtocolError>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let pending = self.pending.get_or_insert_with(|| {
+            let ctx = self.ctx.clone();
+            let ret_r = self.ret_r.clone();
+            Box::pin(async move {
+                let fut_return = async {
+                    let out = ret_r.recv().await.expect("failed to retrieve return value");
+                    Ok::<_, ProtocolError>(Action::Return(out?))
+                };
+                let fut_outgoing = async {
+                    let action = match ctx.comms.outgoing().await? {
+                        Message::Many(m) => Action::SendMany(m),
+                        Message::Private(to, m) => Action::SendPrivate(to, m),
+                    };
+                    Ok::<_, ProtocolError>(action)
+                };
+                let fut_failure = async { Err(ctx.comms.failure().await) };
+                // Unlike when poking, we never need to return `Action::Wait`, since
+                // the executor will wake us up once a new message lets us make progress.
+                ctx.run(future::or(
+                    fut_outgoing,
+                    future::or(fut_failure, fut_return),
+                ))
+                .await
+            })
+        });
+        let action = ready!(pending.as_mut().poll(cx));
+        self.pending = None;
+        if !matches!(
+            action,
+            Ok(Action::SendMany(_)) | Ok(Action::SendPrivate(..))
+        ) {
+            self.done = true;
+        }
+        Poll::Ready(Some(action))
+    }
+
+    fn waiting_on(&self) -> Vec<Waiting> {
+        self.ctx.comms.waiting.snapshot()
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        Some(self.ctx.comms.journal.snapshot(&self.ctx.session_id))
+    }
+
+    fn deliver(&mut self, from: Participant, data: MessageData) {
+        // Spawning the task will wake up the pending action, if there is one.
+        let comms = self.ctx.comms.clone();
+        self.ctx
+            .spawn(async move { comms.push_message(from, data).await })
+            .detach();
+    }
 }
 
 /// Run a protocol, converting a future into an instance of the Protocol trait.
@@ -554,3 +1160,276 @@ pub fn make_protocol<'a, T: Send + 'a>(
 ) -> impl Protocol<Output = T> + 'a {
     ProtocolExecutor::new(ctx, fut)
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use smol::stream::StreamExt;
+
+    use super::*;
+
+    fn echo_protocol(session_id: &[u8], other: Participant) -> impl Protocol<Output = u32> {
+        let ctx = Context::new(ProtocolId::Keygen, session_id);
+        let fut = {
+            let mut chan = ctx.shared_channel();
+            async move {
+                let wait0 = chan.next_waitpoint();
+                chan.send_many(wait0, &1u32).await;
+                let (_, x): (_, u32) = chan.recv(wait0, [other]).await?;
+                Ok(x)
+            }
+        };
+        make_protocol(ctx, fut)
+    }
+
+    #[test]
+    fn test_sessions_are_separated() -> Result<(), ProtocolError> {
+        let p0 = Participant::from(0u32);
+        let p1 = Participant::from(1u32);
+
+        let mut prot0 = echo_protocol(b"session 0", p1);
+        let mut prot1 = echo_protocol(b"session 1", p0);
+
+        let m0 = match prot0.poke()? {
+            Action::SendMany(m) => m,
+            _ => panic!("expected a message from the first protocol"),
+        };
+        let m1 = match prot1.poke()? {
+            Action::SendMany(m) => m,
+            _ => panic!("expected a message from the second protocol"),
+        };
+        prot0.message(p1, m1);
+        prot1.message(p0, m0);
+
+        // Neither protocol should accept the message from the other session.
+        assert!(matches!(prot0.poke()?, Action::Wait));
+        assert!(matches!(prot1.poke()?, Action::Wait));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_layout() {
+        let header = MessageHeader {
+            channel: ChannelTag([7u8; ChannelTag::SIZE]),
+            waitpoint: 3,
+        };
+        let envelope = Envelope {
+            protocol: ProtocolId::Presign,
+            codec: Codec::MessagePack,
+            header,
+        };
+        let data = envelope.seal(&1u32);
+
+        // The wire version always comes first, no matter the version.
+        assert_eq!(data[..2], WIRE_VERSION.to_le_bytes());
+        assert_eq!(data[2], 7);
+        assert_eq!(data[3], 1);
+        assert_eq!(data[4..4 + MessageHeader::LEN], header.to_bytes());
+        assert_eq!(data[Envelope::LEN - 4..Envelope::LEN], 1u32.to_le_bytes());
+        assert_eq!(data[Envelope::LEN..], [1]);
+        assert_eq!(
+            Envelope::open(ProtocolId::Presign, Codec::MessagePack, &data).unwrap(),
+            (header, &[1u8][..])
+        );
+
+        assert!(matches!(
+            Envelope::open(ProtocolId::Presign, Codec::Canonical, &data),
+            Err(EnvelopeError::Codec(1))
+        ));
+
+        // The length needs to match the payload exactly.
+        let mut longer = data.clone();
+        longer.push(0);
+        assert!(matches!(
+            Envelope::open(ProtocolId::Presign, Codec::MessagePack, &longer),
+            Err(EnvelopeError::Malformed)
+        ));
+        assert!(matches!(
+            Envelope::open(
+                ProtocolId::Presign,
+                Codec::MessagePack,
+                &data[..Envelope::LEN]
+            ),
+            Err(EnvelopeError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_incompatible_messages() -> Result<(), ProtocolError> {
+        let p0 = Participant::from(0u32);
+        let p1 = Participant::from(1u32);
+
+        let mut prot1 = echo_protocol(b"test incompatible messages", p0);
+        let m1 = match prot1.poke()? {
+            Action::SendMany(m) => m,
+            _ => panic!("expected a message"),
+        };
+
+        // A message from a different version of the wire format is reported as such,
+        // even if the rest of its envelope has changed completely.
+        let mut prot0 = echo_protocol(b"test incompatible messages", p1);
+        let mut other_version = (WIRE_VERSION + 1).to_le_bytes().to_vec();
+        other_version.push(0);
+        prot0.message(p1, other_version);
+        match prot0.poke() {
+            Err(ProtocolError::VersionMismatch {
+                participant,
+                ours,
+                theirs,
+            }) => {
+                assert_eq!(participant, p1);
+                assert_eq!(ours, WIRE_VERSION);
+                assert_eq!(theirs, WIRE_VERSION + 1);
+            }
+            _ => panic!("expected a version mismatch"),
+        }
+
+        // The same goes for a message from a different protocol.
+        let mut prot0 = echo_protocol(b"test incompatible messages", p1);
+        let mut other_protocol = m1.clone();
+        other_protocol[2] = ProtocolId::Sign as u8;
+        prot0.message(p1, other_protocol);
+        assert!(matches!(
+            prot0.poke(),
+            Err(ProtocolError::ProtocolMismatch {
+                ours: ProtocolId::Keygen,
+                theirs: 8,
+                ..
+            })
+        ));
+
+        // Malformed envelopes are ignored, while the correct message goes through.
+        let mut prot0 = echo_protocol(b"test incompatible messages", p1);
+        prot0.message(p1, m1[..Envelope::LEN - 1].to_vec());
+        assert!(matches!(prot0.poke()?, Action::SendMany(_)));
+        assert!(matches!(prot0.poke()?, Action::Wait));
+        prot0.message(p1, m1);
+        assert!(matches!(prot0.poke()?, Action::Return(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_waiting_on() -> Result<(), ProtocolError> {
+        let p0 = Participant::from(0u32);
+        let p1 = Participant::from(1u32);
+
+        let mut prot0 = echo_protocol(b"test waiting on", p1);
+        let mut prot1 = echo_protocol(b"test waiting on", p0);
+        assert!(prot0.waiting_on().is_empty());
+
+        assert!(matches!(prot0.poke()?, Action::SendMany(_)));
+        assert!(matches!(prot0.poke()?, Action::Wait));
+        let waiting = prot0.waiting_on();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].participants, vec![p1]);
+
+        let m1 = match prot1.poke()? {
+            Action::SendMany(m) => m,
+            _ => panic!("expected a message from the second protocol"),
+        };
+        prot0.message(p1, m1);
+        assert!(matches!(prot0.poke()?, Action::Return(1)));
+        assert!(prot0.waiting_on().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_ignores_extra_messages() -> Result<(), ProtocolError> {
+        let p0 = Participant::from(0u32);
+        let p1 = Participant::from(1u32);
+
+        let mut prot0 = echo_protocol(b"test journal", p1);
+        let mut prot1 = echo_protocol(b"test journal", p0);
+        assert!(matches!(prot0.poke()?, Action::SendMany(_)));
+        let m1 = match prot1.poke()? {
+            Action::SendMany(m) => m,
+            _ => panic!("expected a message from the second protocol"),
+        };
+        let (header, _) = Envelope::open(ProtocolId::Keygen, Codec::MessagePack, &m1).unwrap();
+        let seal = |waitpoint, x: u32| {
+            Envelope {
+                protocol: ProtocolId::Keygen,
+                codec: Codec::MessagePack,
+                header: header.with_waitpoint(waitpoint),
+            }
+            .seal(&x)
+        };
+
+        // Only the first message at a header counts.
+        prot0.message(p1, m1.clone());
+        prot0.message(p1, m1);
+        prot0.message(p1, seal(0, 2));
+        // Messages at headers we never wait on are only held up to a limit.
+        for waitpoint in 1..MAX_RECEIVED_PER_PARTICIPANT as u64 + 10 {
+            prot0.message(p1, seal(waitpoint, 3));
+        }
+        assert_eq!(
+            prot0.snapshot().unwrap().received.len(),
+            MAX_RECEIVED_PER_PARTICIPANT
+        );
+        assert!(matches!(prot0.poke()?, Action::Return(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlapping_waits() {
+        let p0 = Participant::from(0u32);
+        let p1 = Participant::from(1u32);
+        let header = MessageHeader::from_bytes(&[0u8; MessageHeader::LEN]).unwrap();
+
+        let registry = WaitingRegistry::default();
+        let guard0 = registry.wait(header, vec![p1]);
+        let guard1 = registry.wait(header, vec![p0, p1]);
+        let waiting = registry.snapshot();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].participants, vec![p0, p1]);
+
+        // Each guard only removes its own wait.
+        drop(guard1);
+        let waiting = registry.snapshot();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].participants, vec![p1]);
+
+        drop(guard0);
+        assert!(registry.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_dropping_async_protocol_cancels_it() {
+        struct SetOnDrop(Arc<AtomicBool>);
+
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let ctx = Context::new(ProtocolId::Keygen, b"test cancel");
+        let fut = {
+            let mut chan = ctx.shared_channel();
+            let guard = SetOnDrop(dropped.clone());
+            async move {
+                let _guard = guard;
+                let wait0 = chan.next_waitpoint();
+                chan.send_many(wait0, &1u32).await;
+                // No one will ever answer us.
+                let (_, x): (_, u32) = chan.recv(wait0, [Participant::from(1u32)]).await?;
+                Ok(x)
+            }
+        };
+        let mut prot = make_protocol(ctx, fut).into_async();
+
+        let action = block_on(prot.next());
+        assert!(matches!(action, Some(Ok(Action::SendMany(_)))));
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        drop(prot);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
@@ -6,26 +6,195 @@
 //! a result, without you having to worry about how many rounds it has, or how
 //! to serialize the emssages it produces.
 use core::fmt;
-use std::{collections::HashMap, error};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    error,
+    pin::Pin,
+    task::{self, Poll, Waker},
+};
 
-use ::serde::Serialize;
+use ::serde::{Deserialize, Serialize};
+use futures_core::Stream;
+use futures_sink::Sink;
+use rand_core::CryptoRngCore;
+use zeroize::Zeroize;
 
 use crate::compat::CSCurve;
+pub use crate::serde::Codec;
+
+/// The ways in which a participant can be caught misbehaving during a protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Misbehavior {
+    /// The participant sent a message we couldn't decode, or which had the wrong shape.
+    MalformedMessage,
+    /// The participant revealed a value which didn't match their commitment.
+    BadCommitmentOpening,
+    /// The participant revealed a polynomial with the wrong degree, or the wrong constant term.
+    BadPolynomial,
+    /// The participant sent a discrete logarithm proof which didn't verify.
+    BadDlogProof,
+    /// The participant sent a discrete logarithm equality proof which didn't verify.
+    BadDlogEqProof,
+    /// The participant's hash of everyone's commitments didn't match ours.
+    ///
+    /// This can also mean that some other participant sent different commitments
+    /// to different parties.
+    InconsistentConfirmation,
+    /// The participant privately sent us a share which didn't match their public commitment.
+    BadPrivateShare,
+    /// The participant broadcast a value which didn't match their commitments to their shares.
+    BadPublicShare,
+}
+
+impl fmt::Display for Misbehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Misbehavior::MalformedMessage => "sent a malformed message",
+            Misbehavior::BadCommitmentOpening => "sent a bad commitment opening",
+            Misbehavior::BadPolynomial => "sent a bad polynomial",
+            Misbehavior::BadDlogProof => "sent a bad dlog proof",
+            Misbehavior::BadDlogEqProof => "sent a bad dlogeq proof",
+            Misbehavior::InconsistentConfirmation => "sent an inconsistent confirmation",
+            Misbehavior::BadPrivateShare => "sent a bad private share",
+            Misbehavior::BadPublicShare => "sent a bad public share",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The version of the wire format used by the messages of every protocol.
+///
+/// Every message starts with this version, encoded as 2 little endian bytes,
+/// which lets participants running incompatible versions of this library
+/// notice this right away, with [ProtocolError::VersionMismatch].
+/// See `docs/wire-format.md` for when this changes.
+pub const WIRE_VERSION: u16 = 3;
+
+/// Identifies the protocol a message belongs to.
+///
+/// Along with [WIRE_VERSION], this is part of the envelope of every message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProtocolId {
+    Keygen = 1,
+    KeygenMany = 2,
+    ImportKey = 3,
+    Reshare = 4,
+    TripleGeneration = 5,
+    TripleGenerationMany = 6,
+    Presign = 7,
+    Sign = 8,
+    SignSchnorr = 9,
+    RepairShare = 10,
+    ExportKey = 11,
+    PresignWithBlame = 12,
+}
+
+impl fmt::Display for ProtocolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ProtocolId::Keygen => "keygen",
+            ProtocolId::KeygenMany => "keygen_many",
+            ProtocolId::ImportKey => "import_key",
+            ProtocolId::Reshare => "reshare",
+            ProtocolId::TripleGeneration => "generate_triple",
+            ProtocolId::TripleGenerationMany => "generate_triple_many",
+            ProtocolId::Presign => "presign",
+            ProtocolId::Sign => "sign",
+            ProtocolId::SignSchnorr => "sign_schnorr",
+            ProtocolId::RepairShare => "repair_share",
+            ProtocolId::ExportKey => "export_key",
+            ProtocolId::PresignWithBlame => "presign_with_blame",
+        };
+        write!(f, "{}", s)
+    }
+}
 
 /// Represents an error which can happen when running a protocol.
 #[derive(Debug)]
 pub enum ProtocolError {
     /// Some assertion in the protocol failed.
     AssertionFailed(String),
+    /// A specific participant was caught misbehaving, causing the protocol to abort.
+    Misbehaved {
+        participant: Participant,
+        reason: Misbehavior,
+    },
+    /// A participant sent a message with a different version of the wire format.
+    ///
+    /// This means they're running a version of this library which isn't compatible
+    /// with ours, and every participant needs to be upgraded before trying again.
+    VersionMismatch {
+        participant: Participant,
+        ours: u16,
+        theirs: u16,
+    },
+    /// A participant sent a message for a different protocol, in the same session.
+    ///
+    /// The id of their protocol is kept as is, since it might be one we don't know.
+    ProtocolMismatch {
+        participant: Participant,
+        ours: ProtocolId,
+        theirs: u8,
+    },
+    /// A participant sent a message encoded with a different [Codec].
+    ///
+    /// Every participant needs to use the same codec, see [Protocol::set_codec].
+    CodecMismatch {
+        participant: Participant,
+        ours: Codec,
+        theirs: u8,
+    },
     /// Some generic error happened.
     Other(Box<dyn error::Error + Send + Sync>),
 }
 
+impl ProtocolError {
+    /// The participant responsible for this error, if they could be identified.
+    pub fn culprit(&self) -> Option<Participant> {
+        match self {
+            ProtocolError::Misbehaved { participant, .. } => Some(*participant),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for ProtocolError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ProtocolError::Other(e) => write!(f, "{}", e),
             ProtocolError::AssertionFailed(e) => write!(f, "assertion failed {}", e),
+            ProtocolError::Misbehaved {
+                participant,
+                reason,
+            } => write!(f, "participant {:?} {}", participant, reason),
+            ProtocolError::VersionMismatch {
+                participant,
+                ours,
+                theirs,
+            } => write!(
+                f,
+                "participant {:?} uses wire version {}, but we use {}",
+                participant, theirs, ours
+            ),
+            ProtocolError::ProtocolMismatch {
+                participant,
+                ours,
+                theirs,
+            } => write!(
+                f,
+                "participant {:?} sent a message for protocol {}, but we're running {}",
+                participant, theirs, ours
+            ),
+            ProtocolError::CodecMismatch {
+                participant,
+                ours,
+                theirs,
+            } => write!(
+                f,
+                "participant {:?} sent a message with codec {}, but we use {:?}",
+                participant, theirs, ours
+            ),
         }
     }
 }
@@ -64,7 +233,7 @@ impl error::Error for InitializationError {}
 /// struct holds. In our case, we use a `u32`, which is enough for billions of
 /// participants. That said, you won't actually be able to make the protocols
 /// work with billions of users.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
 pub struct Participant(u32);
 
 impl Participant {
@@ -97,6 +266,51 @@ impl From<u32> for Participant {
 /// the serialization logic handled internally.
 pub type MessageData = Vec<u8>;
 
+/// Identifies a point in a protocol where messages are received.
+///
+/// This is opaque, and only useful to tell different waitpoints apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WaitpointId([u8; 28]);
+
+/// A point in a protocol which is blocked on messages from some participants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Waiting {
+    /// The point in the protocol we're blocked at.
+    pub waitpoint: WaitpointId,
+    /// The participants we're still waiting on a message from, in sorted order.
+    pub participants: Vec<Participant>,
+}
+
+/// A snapshot of the progress of a protocol, allowing it to be resumed later.
+///
+/// This is created with [Protocol::snapshot], and can be used to resume the
+/// protocol with [Protocol::restore], e.g. after restarting the process.
+///
+/// **WARNING** This contains the secret seed all of the protocol's randomness is
+/// derived from, along with every message it received, including private ones.
+/// This needs to be stored as carefully as the secret outputs of the protocol.
+/// Only the most recent snapshot can be safely restored, see [Protocol::poke_with_snapshot].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub(crate) session_id: Vec<u8>,
+    pub(crate) seed: [u8; 32],
+    pub(crate) codec: Codec,
+    pub(crate) received: Vec<(Participant, MessageData)>,
+    pub(crate) sent: Vec<(Option<Participant>, MessageData)>,
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+        for (_, data) in &mut self.received {
+            data.zeroize();
+        }
+        for (_, data) in &mut self.sent {
+            data.zeroize();
+        }
+    }
+}
+
 /// Represents an action by a participant in the protocol.
 ///
 /// The basic flow is that each participant receives messages from other participants,
@@ -141,6 +355,251 @@ pub trait Protocol {
 
     /// Inform the protocol of a new message.
     fn message(&mut self, from: Participant, data: MessageData);
+
+    /// Return the participants the protocol is currently waiting on, at each waitpoint.
+    ///
+    /// This is most useful after the protocol returns [Action::Wait], to find
+    /// out which participants have stalled the protocol. Protocols which don't
+    /// keep track of this simply return nothing.
+    fn waiting_on(&self) -> Vec<Waiting> {
+        Vec::new()
+    }
+
+    /// Export a snapshot of the progress of this protocol.
+    ///
+    /// This returns `None` if the protocol doesn't support snapshots.
+    /// The snapshot includes every message the protocol has produced so far.
+    fn snapshot(&self) -> Option<Snapshot> {
+        None
+    }
+
+    /// Poke the protocol, returning a snapshot to store along with each message to send.
+    ///
+    /// A restored protocol derives the same secret randomness as it did before.
+    /// If it were restored from a snapshot older than a message it sent, another
+    /// participant could answer that message differently, and the protocol would
+    /// answer again with the same secret nonces, which can leak its secret shares.
+    ///
+    /// To avoid this, whenever this returns an action sending a message, it also
+    /// returns a snapshot which already includes that message. This snapshot needs
+    /// to be stored durably, replacing the previous one, *before* the message is sent.
+    /// For other actions, or protocols which don't support snapshots, this returns `None`.
+    fn poke_with_snapshot(
+        &mut self,
+    ) -> Result<(Action<Self::Output>, Option<Snapshot>), ProtocolError> {
+        let action = self.poke()?;
+        let snapshot = match action {
+            Action::SendMany(_) | Action::SendPrivate(..) => self.snapshot(),
+            _ => None,
+        };
+        Ok((action, snapshot))
+    }
+
+    /// Draw the secret seed of this protocol from a given source of randomness.
+    ///
+    /// By default, protocols are seeded from the operating system. Seeding every
+    /// participant with a deterministic rng makes a run of the protocol reproducible,
+    /// which is useful for testing, but the seed must otherwise remain secret.
+    /// This needs to be called on a fresh protocol, before poking it.
+    fn seed_from_rng(&mut self, _rng: &mut dyn CryptoRngCore) -> Result<(), InitializationError> {
+        Err(InitializationError::BadParameters(
+            "this protocol doesn't support seeding".to_string(),
+        ))
+    }
+
+    /// Change the codec used to encode and decode messages.
+    ///
+    /// Every participant needs to use the same codec. Like [Protocol::seed_from_rng],
+    /// this needs to be called on a fresh protocol, before poking it.
+    /// Restoring a snapshot uses the codec the snapshot was taken with.
+    fn set_codec(&mut self, _codec: Codec) -> Result<(), InitializationError> {
+        Err(InitializationError::BadParameters(
+            "this protocol doesn't support other codecs".to_string(),
+        ))
+    }
+
+    /// Resume the progress saved in a snapshot.
+    ///
+    /// This needs to be called on a fresh protocol, created with exactly the same
+    /// arguments as the protocol the snapshot was taken from, before poking it.
+    /// The protocol will then replay the messages it received, and produce the
+    /// messages in the snapshot again, since they might not have been delivered.
+    /// Other participants ignore the messages they've already received.
+    /// If the protocol were to produce a message different from one in the snapshot,
+    /// it will fail instead.
+    ///
+    /// **WARNING** This only knows about the messages in the snapshot, so the snapshot
+    /// needs to be the most recent one, stored as described in [Protocol::poke_with_snapshot].
+    /// Restoring an older snapshot, missing some of the messages the protocol sent,
+    /// can leak its secrets.
+    fn restore(&mut self, _snapshot: Snapshot) -> Result<(), InitializationError> {
+        Err(InitializationError::BadParameters(
+            "this protocol doesn't support snapshots".to_string(),
+        ))
+    }
+
+    /// Convert this protocol into one which can be driven asynchronously.
+    ///
+    /// The protocols in this library are driven natively, without blocking,
+    /// but other implementations of this trait will simply get poked whenever
+    /// a new message arrives.
+    fn into_async<'a>(self) -> AsyncProtocol<'a, Self::Output>
+    where
+        Self: Sized + Send + 'a,
+        Self::Output: Send + 'a,
+    {
+        AsyncProtocol::new(Poked {
+            protocol: self,
+            waker: None,
+            done: false,
+        })
+    }
+}
+
+/// The internal interface used to drive an [AsyncProtocol].
+trait Drive<T>: Send {
+    /// Try and produce the next action, which will never be [Action::Wait].
+    fn poll_action(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<Action<T>, ProtocolError>>>;
+
+    /// Return the participants the protocol is currently waiting on.
+    fn waiting_on(&self) -> Vec<Waiting>;
+
+    /// Export a snapshot of the progress of the protocol.
+    fn snapshot(&self) -> Option<Snapshot>;
+
+    /// Deliver a new message to the protocol.
+    fn deliver(&mut self, from: Participant, data: MessageData);
+}
+
+/// Drives an arbitrary protocol, by poking it each time a message arrives.
+struct Poked<P> {
+    protocol: P,
+    waker: Option<Waker>,
+    done: bool,
+}
+
+impl<P: Protocol + Send> Drive<P::Output> for Poked<P> {
+    fn poll_action(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<Action<P::Output>, ProtocolError>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match self.protocol.poke() {
+            Ok(Action::Wait) => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Ok(action @ (Action::SendMany(_) | Action::SendPrivate(..))) => {
+                Poll::Ready(Some(Ok(action)))
+            }
+            res => {
+                self.done = true;
+                Poll::Ready(Some(res))
+            }
+        }
+    }
+
+    fn waiting_on(&self) -> Vec<Waiting> {
+        self.protocol.waiting_on()
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        self.protocol.snapshot()
+    }
+
+    fn deliver(&mut self, from: Participant, data: MessageData) {
+        self.protocol.message(from, data);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A protocol which can be driven asynchronously, e.g. inside of a tokio runtime.
+///
+/// This is created with [Protocol::into_async].
+///
+/// The actions of the protocol are produced as a [Stream], which will never yield
+/// [Action::Wait], and ends after yielding either [Action::Return] or an error.
+/// Messages from other participants are delivered through the [Sink] implementation,
+/// which is always ready to accept more messages.
+///
+/// No work happens unless the stream is polled, and dropping this struct cancels
+/// the protocol.
+pub struct AsyncProtocol<'a, T> {
+    inner: Box<dyn Drive<T> + 'a>,
+}
+
+impl<'a, T> AsyncProtocol<'a, T> {
+    fn new(inner: impl Drive<T> + 'a) -> Self {
+        Self {
+            inner: Box::new(inner),
+        }
+    }
+
+    /// Return the participants the protocol is currently waiting on, at each waitpoint.
+    ///
+    /// See [Protocol::waiting_on].
+    pub fn waiting_on(&self) -> Vec<Waiting> {
+        self.inner.waiting_on()
+    }
+
+    /// Export a snapshot of the progress of the protocol.
+    ///
+    /// See [Protocol::snapshot]. To restore a protocol, use [Protocol::restore]
+    /// before converting it into an async protocol.
+    ///
+    /// When the stream yields a message to send, this snapshot already includes it,
+    /// and needs to be stored before sending it, as with [Protocol::poke_with_snapshot].
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        self.inner.snapshot()
+    }
+}
+
+impl<'a, T> Stream for AsyncProtocol<'a, T> {
+    type Item = Result<Action<T>, ProtocolError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_action(cx)
+    }
+}
+
+impl<'a, T> Sink<(Participant, MessageData)> for AsyncProtocol<'a, T> {
+    type Error = Infallible;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (from, data): (Participant, MessageData),
+    ) -> Result<(), Self::Error> {
+        self.inner.deliver(from, data);
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        _cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
 }
 
 /// Run a protocol to completion, synchronously.
@@ -237,4 +696,74 @@ pub(crate) fn run_two_party_protocol<T0: std::fmt::Debug, T1: std::fmt::Debug>(
     Ok((out0.unwrap(), out1.unwrap()))
 }
 
+/// A wrapper around a protocol, which flips a bit in some of the messages it sends.
+///
+/// This is useful for testing that misbehavior gets caught.
+#[cfg(test)]
+pub(crate) struct Corrupt<P, F> {
+    pub protocol: P,
+    /// Decides whether or not to corrupt the message sent by a given action.
+    pub should_corrupt: F,
+}
+
+#[cfg(test)]
+impl<P: Protocol, F: FnMut(&Action<P::Output>) -> bool> Protocol for Corrupt<P, F> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        let mut action = self.protocol.poke()?;
+        if (self.should_corrupt)(&action) {
+            match &mut action {
+                Action::SendMany(m) | Action::SendPrivate(_, m) => *m.last_mut().unwrap() ^= 1,
+                _ => {}
+            }
+        }
+        Ok(action)
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.protocol.message(from, data)
+    }
+
+    fn waiting_on(&self) -> Vec<Waiting> {
+        self.protocol.waiting_on()
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        self.protocol.snapshot()
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), InitializationError> {
+        self.protocol.restore(snapshot)
+    }
+}
+
+/// A wrapper around a protocol, which can modify the actions it returns.
+///
+/// Unlike [`Corrupt`], this can change a message in a precise way, e.g. with
+/// [`internal::tamper_payload`], to trigger a specific check.
+#[cfg(test)]
+pub(crate) struct Tamper<P, F> {
+    pub protocol: P,
+    pub tamper: F,
+}
+
+#[cfg(test)]
+impl<P: Protocol, F: FnMut(&mut Action<P::Output>)> Protocol for Tamper<P, F> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        let mut action = self.protocol.poke()?;
+        (self.tamper)(&mut action);
+        Ok(action)
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.protocol.message(from, data)
+    }
+}
+
 pub(crate) mod internal;
+mod mux;
+
+pub use mux::{MuxAction, ProtocolMux};
@@ -0,0 +1,532 @@
+//! This module provides a way to run many instances of protocols at once.
+//!
+//! Often, the same set of participants will want to run many protocols concurrently,
+//! e.g. generating many triples, or creating many presignatures, over a single
+//! connection. The messages produced by a protocol don't say which instance
+//! they belong to, so [ProtocolMux] adds this information, by framing each message
+//! with an instance id, and then routes incoming messages to the right instance.
+//!
+//! Like the envelope of a message, this frame starts with the wire version,
+//! see `docs/wire-format.md`.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::serde::Codec;
+
+use super::{
+    Action, InitializationError, MessageData, Participant, Protocol, ProtocolError, Waiting,
+    WIRE_VERSION,
+};
+
+/// The number of bytes before the encoding of an instance id: the wire version, and its length.
+const FRAME_PREFIX_LEN: usize = 2 + 4;
+
+/// The default number of messages held for a single instance which hasn't been added yet.
+const DEFAULT_MAX_PENDING_PER_INSTANCE: usize = 1 << 10;
+
+/// The default number of messages held across all instances which haven't been added yet.
+const DEFAULT_MAX_PENDING: usize = 1 << 16;
+
+/// Frame a message with an instance id.
+///
+/// The id is encoded with [Codec::Canonical], so that ids of a fixed size type
+/// always add the same number of bytes.
+fn frame<K: Serialize>(id: &K, data: &[u8]) -> MessageData {
+    let mut out = Codec::Canonical.encode_with_tag(&[0u8; FRAME_PREFIX_LEN], id);
+    let id_len = u32::try_from(out.len() - FRAME_PREFIX_LEN).expect("instance id is too large");
+    out[..2].copy_from_slice(&WIRE_VERSION.to_le_bytes());
+    out[2..FRAME_PREFIX_LEN].copy_from_slice(&id_len.to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+/// Split a framed message into the encoding of an instance id, and the message.
+///
+/// This doesn't check the wire version, which is also the start of the message
+/// itself, so that the instance can fail with [ProtocolError::VersionMismatch].
+fn unframe(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if data.len() < FRAME_PREFIX_LEN {
+        return None;
+    }
+    let (prefix, rest) = data.split_at(FRAME_PREFIX_LEN);
+    // Unwrapping is fine because we checked the length already.
+    let len = usize::try_from(u32::from_le_bytes(prefix[2..].try_into().unwrap())).ok()?;
+    if rest.len() < len {
+        return None;
+    }
+    Some(rest.split_at(len))
+}
+
+/// Represents an action by the multiplexer.
+///
+/// This is like [Action], except that it also says which instance finished.
+#[derive(Debug)]
+pub enum MuxAction<K, T> {
+    /// Don't do anything, because no instance can make progress.
+    Wait,
+    /// Send a message to all other participants.
+    SendMany(MessageData),
+    /// Send a private message to another participant.
+    SendPrivate(Participant, MessageData),
+    /// An instance finished, returning a value.
+    Return(K, T),
+    /// An instance failed, with an error.
+    ///
+    /// Like with [Action::Return], this instance is no longer part of the multiplexer.
+    Failed(K, ProtocolError),
+}
+
+/// Runs many instances of protocols at once, identified by an instance id.
+///
+/// The messages this produces are framed with the instance id they belong to,
+/// so all participants need to use the same id for the same instance,
+/// and need to pass every message they receive to this multiplexer.
+///
+/// Messages for an instance which hasn't been added yet are held onto until
+/// it gets added, since other participants might start an instance before us.
+/// Since anyone can send messages for any id, only a limited number of these
+/// are held, see [ProtocolMux::with_pending_limits].
+/// Messages for instances which have already finished are dropped,
+/// until they're forgotten with [ProtocolMux::forget].
+pub struct ProtocolMux<K, T> {
+    protocols: BTreeMap<K, Box<dyn Protocol<Output = T> + Send>>,
+    /// Messages received for instances we haven't been added yet.
+    pending: BTreeMap<K, Vec<(Participant, MessageData)>>,
+    /// The number of messages in `pending`, across all instances.
+    pending_len: usize,
+    /// The most messages held in `pending` for a single instance.
+    max_pending_per_instance: usize,
+    /// The most messages held in `pending` across all instances.
+    max_pending: usize,
+    /// The instances which have finished.
+    finished: BTreeSet<K>,
+    /// The last instance we poked, so that each instance gets a fair chance.
+    last: Option<K>,
+}
+
+impl<K, T> Default for ProtocolMux<K, T> {
+    fn default() -> Self {
+        Self {
+            protocols: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            pending_len: 0,
+            max_pending_per_instance: DEFAULT_MAX_PENDING_PER_INSTANCE,
+            max_pending: DEFAULT_MAX_PENDING,
+            finished: BTreeSet::new(),
+            last: None,
+        }
+    }
+}
+
+impl<K, T> ProtocolMux<K, T>
+where
+    K: Ord + Clone + Serialize + DeserializeOwned,
+{
+    /// Create a multiplexer without any instances.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a multiplexer which holds a given number of messages for instances
+    /// which haven't been added yet.
+    ///
+    /// At most `per_instance` messages are held for each of these instances,
+    /// and at most `total` across all of them, with any further messages being dropped.
+    /// By default, these limits are 1024 and 65536 messages.
+    pub fn with_pending_limits(per_instance: usize, total: usize) -> Self {
+        Self {
+            max_pending_per_instance: per_instance,
+            max_pending: total,
+            ..Self::default()
+        }
+    }
+
+    /// Add a new instance, with a given id.
+    ///
+    /// This will fail if that id is already used by another instance,
+    /// including one which already finished.
+    pub fn add(
+        &mut self,
+        id: K,
+        protocol: impl Protocol<Output = T> + Send + 'static,
+    ) -> Result<(), InitializationError> {
+        if self.protocols.contains_key(&id) || self.finished.contains(&id) {
+            return Err(InitializationError::BadParameters(
+                "instance id is already in use".to_string(),
+            ));
+        }
+        let mut protocol: Box<dyn Protocol<Output = T> + Send> = Box::new(protocol);
+        for (from, data) in self.take_pending(&id) {
+            protocol.message(from, data);
+        }
+        self.protocols.insert(id, protocol);
+        Ok(())
+    }
+
+    /// The number of instances which haven't finished yet.
+    pub fn len(&self) -> usize {
+        self.protocols.len()
+    }
+
+    /// Check whether or not every instance has finished.
+    pub fn is_empty(&self) -> bool {
+        self.protocols.is_empty()
+    }
+
+    /// Poke the instances, until one of them has an action.
+    ///
+    /// This returns [MuxAction::Wait] only once no instance can make progress.
+    pub fn poke(&mut self) -> MuxAction<K, T> {
+        // Start after the instance we poked last, wrapping around.
+        let ids: Vec<K> = match &self.last {
+            None => self.protocols.keys().cloned().collect(),
+            Some(last) => self
+                .protocols
+                .range((Bound::Excluded(last), Bound::Unbounded))
+                .chain(self.protocols.range(..=last))
+                .map(|(id, _)| id.clone())
+                .collect(),
+        };
+        for id in ids {
+            let action = self.protocols.get_mut(&id).unwrap().poke();
+            self.last = Some(id.clone());
+            let framed = |data: MessageData| frame(&id, &data);
+            match action {
+                Ok(Action::Wait) => continue,
+                Ok(Action::SendMany(data)) => return MuxAction::SendMany(framed(data)),
+                Ok(Action::SendPrivate(to, data)) => {
+                    return MuxAction::SendPrivate(to, framed(data))
+                }
+                Ok(Action::Return(out)) => {
+                    self.finish(&id);
+                    return MuxAction::Return(id, out);
+                }
+                Err(e) => {
+                    self.finish(&id);
+                    return MuxAction::Failed(id, e);
+                }
+            }
+        }
+        MuxAction::Wait
+    }
+
+    fn finish(&mut self, id: &K) {
+        self.protocols.remove(id);
+        self.finished.insert(id.clone());
+    }
+
+    fn take_pending(&mut self, id: &K) -> Vec<(Participant, MessageData)> {
+        let out = self.pending.remove(id).unwrap_or_default();
+        self.pending_len -= out.len();
+        out
+    }
+
+    /// Forget about an instance which has finished, or which was never added.
+    ///
+    /// The multiplexer remembers the id of every instance which finished, in order
+    /// to drop late messages for it, and to refuse adding it again. On a long
+    /// running node, this lets you forget ids which won't be used anymore,
+    /// along with any messages held for them. This does nothing to an instance
+    /// which is still running.
+    ///
+    /// Late messages for a forgotten id are held like messages for an instance
+    /// which hasn't been added yet, so ids shouldn't be reused after forgetting them.
+    pub fn forget(&mut self, id: &K) {
+        self.finished.remove(id);
+        self.take_pending(id);
+    }
+
+    /// Inform the multiplexer of a new message, passing it on to the right instance.
+    ///
+    /// Messages which aren't framed correctly are ignored, as are messages for
+    /// an instance which hasn't been added yet, once the limits on held messages are reached.
+    pub fn message(&mut self, from: Participant, data: MessageData) {
+        let (id, data) = match unframe(&data) {
+            Some(x) => x,
+            None => return,
+        };
+        let id: K = match Codec::Canonical.decode(id) {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(protocol) = self.protocols.get_mut(&id) {
+            protocol.message(from, data.to_vec());
+        } else if !self.finished.contains(&id) {
+            let held = self.pending.get(&id).map_or(0, Vec::len);
+            if held >= self.max_pending_per_instance || self.pending_len >= self.max_pending {
+                return;
+            }
+            self.pending
+                .entry(id)
+                .or_default()
+                .push((from, data.to_vec()));
+            self.pending_len += 1;
+        }
+    }
+
+    /// Return what each instance which hasn't finished is waiting on.
+    ///
+    /// See [Protocol::waiting_on].
+    pub fn waiting_on(&self) -> Vec<(K, Vec<Waiting>)> {
+        self.protocols
+            .iter()
+            .map(|(id, protocol)| (id.clone(), protocol.waiting_on()))
+            .filter(|(_, waiting)| !waiting.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{keygen, protocol::Corrupt, KeygenOutput};
+
+    /// Run a set of multiplexers until none can make progress,
+    /// collecting the output of each instance.
+    #[allow(clippy::type_complexity)]
+    fn run_muxes<K: Ord + Clone + Serialize + DeserializeOwned, T>(
+        mut muxes: Vec<(Participant, ProtocolMux<K, T>)>,
+    ) -> Vec<(Participant, K, Result<T, ProtocolError>)> {
+        let indices: HashMap<Participant, usize> = muxes
+            .iter()
+            .enumerate()
+            .map(|(i, (p, _))| (*p, i))
+            .collect();
+
+        let mut out = Vec::new();
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for i in 0..muxes.len() {
+                let from = muxes[i].0;
+                loop {
+                    let action = muxes[i].1.poke();
+                    if !matches!(action, MuxAction::Wait) {
+                        progress = true;
+                    }
+                    match action {
+                        MuxAction::Wait => break,
+                        MuxAction::SendMany(m) => {
+                            for j in (0..muxes.len()).filter(|j| *j != i) {
+                                muxes[j].1.message(from, m.clone());
+                            }
+                        }
+                        MuxAction::SendPrivate(to, m) => {
+                            muxes[indices[&to]].1.message(from, m);
+                        }
+                        MuxAction::Return(id, x) => out.push((from, id, Ok(x))),
+                        MuxAction::Failed(id, e) => out.push((from, id, Err(e))),
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_mux_runs_instances_concurrently() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let ids = [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+
+        let mut muxes = Vec::new();
+        for p in &participants {
+            let mut mux = ProtocolMux::new();
+            for id in &ids {
+                let protocol = keygen::<Secp256k1>(&participants, *p, 2, id).unwrap();
+                mux.add(id.clone(), protocol).unwrap();
+            }
+            assert!(mux
+                .add(ids[0].clone(), keygen(&participants, *p, 2, b"").unwrap())
+                .is_err());
+            muxes.push((*p, mux));
+        }
+
+        let result = run_muxes(muxes);
+        assert_eq!(result.len(), participants.len() * ids.len());
+        let mut keys: BTreeMap<Vec<u8>, Vec<KeygenOutput<Secp256k1>>> = BTreeMap::new();
+        for (_, id, out) in result {
+            keys.entry(id).or_default().push(out.unwrap());
+        }
+        assert_eq!(keys.len(), ids.len());
+        for outputs in keys.values() {
+            assert_eq!(outputs[0].public_key, outputs[1].public_key);
+        }
+        // Different instances should produce different keys.
+        assert_ne!(keys[&ids[0]][0].public_key, keys[&ids[1]][0].public_key);
+    }
+
+    #[test]
+    fn test_mux_holds_messages_for_later_instances() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let (p0, p1) = (participants[0], participants[1]);
+
+        let mut mux0 = ProtocolMux::new();
+        let mut mux1 = ProtocolMux::new();
+        mux0.add(
+            0u64,
+            keygen::<Secp256k1>(&participants, p0, 2, b"mux").unwrap(),
+        )
+        .unwrap();
+
+        // The first participant starts sending before the second has the instance.
+        let m = match mux0.poke() {
+            MuxAction::SendMany(m) => m,
+            _ => panic!("expected the first participant to send a message"),
+        };
+        mux1.message(p0, m);
+        mux1.add(
+            0u64,
+            keygen::<Secp256k1>(&participants, p1, 2, b"mux").unwrap(),
+        )
+        .unwrap();
+
+        let result = run_muxes(vec![(p0, mux0), (p1, mux1)]);
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|(_, _, out)| out.is_ok()));
+    }
+
+    #[test]
+    fn test_mux_reports_failures_per_instance() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+
+        let mut muxes = Vec::new();
+        for p in &participants {
+            let mut mux = ProtocolMux::new();
+            mux.add(
+                0u64,
+                keygen::<Secp256k1>(&participants, *p, 2, b"good").unwrap(),
+            )
+            .unwrap();
+            // The second participant corrupts their messages in this instance.
+            let bad = *p == participants[1];
+            let protocol = Corrupt {
+                protocol: keygen::<Secp256k1>(&participants, *p, 2, b"bad").unwrap(),
+                should_corrupt: move |_: &Action<_>| bad,
+            };
+            mux.add(1u64, protocol).unwrap();
+            muxes.push((*p, mux));
+        }
+
+        let result = run_muxes(muxes);
+        for (_, id, out) in &result {
+            match id {
+                0 => assert!(out.is_ok()),
+                _ => assert!(out.is_err()),
+            }
+        }
+        assert_eq!(result.iter().filter(|(_, id, _)| *id == 0).count(), 2);
+        assert!(result
+            .iter()
+            .any(|(p, id, _)| *p == participants[0] && *id == 1));
+    }
+
+    #[test]
+    fn test_mux_frame_layout() {
+        let data = frame(&7u64, b"message");
+        // The wire version always comes first.
+        assert_eq!(data[..2], WIRE_VERSION.to_le_bytes());
+        assert_eq!(data[2..FRAME_PREFIX_LEN], 8u32.to_le_bytes());
+        assert_eq!(
+            data[FRAME_PREFIX_LEN..FRAME_PREFIX_LEN + 8],
+            7u64.to_le_bytes()
+        );
+        assert_eq!(data[FRAME_PREFIX_LEN + 8..], *b"message");
+        // Ids of a fixed size type always have the same size.
+        assert_eq!(frame(&u64::MAX, b"message").len(), data.len());
+        assert_eq!(
+            unframe(&data),
+            Some((&7u64.to_le_bytes()[..], &b"message"[..]))
+        );
+        assert_eq!(unframe(&data[..FRAME_PREFIX_LEN + 7]), None);
+    }
+
+    #[test]
+    fn test_mux_reports_version_mismatch() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let (p0, p1) = (participants[0], participants[1]);
+
+        let mut mux0 = ProtocolMux::new();
+        let mut mux1 = ProtocolMux::new();
+        for (p, mux) in [(p0, &mut mux0), (p1, &mut mux1)] {
+            mux.add(
+                0u64,
+                keygen::<Secp256k1>(&participants, p, 2, b"mux").unwrap(),
+            )
+            .unwrap();
+        }
+
+        // Pretend that the first participant runs another version.
+        let mut m = match mux0.poke() {
+            MuxAction::SendMany(m) => m,
+            _ => panic!("expected the first participant to send a message"),
+        };
+        let other_version = (WIRE_VERSION + 1).to_le_bytes();
+        m[..2].copy_from_slice(&other_version);
+        let inner = FRAME_PREFIX_LEN + 8;
+        m[inner..inner + 2].copy_from_slice(&other_version);
+        mux1.message(p0, m);
+
+        loop {
+            match mux1.poke() {
+                MuxAction::Failed(0, ProtocolError::VersionMismatch { participant, .. }) => {
+                    assert_eq!(participant, p0);
+                    break;
+                }
+                MuxAction::SendMany(_) | MuxAction::SendPrivate(..) => continue,
+                other => panic!("expected a version mismatch, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mux_bounds_pending_messages() {
+        let p = Participant::from(0u32);
+        let message = |id: u64| frame(&id, b"message");
+
+        let mut mux: ProtocolMux<u64, ()> = ProtocolMux::with_pending_limits(2, 3);
+        for _ in 0..3 {
+            mux.message(p, message(0));
+        }
+        assert_eq!(mux.pending[&0].len(), 2);
+        mux.message(p, message(1));
+        // The total limit has been reached.
+        mux.message(p, message(2));
+        assert_eq!(mux.pending.len(), 2);
+        assert_eq!(mux.pending_len, 3);
+
+        // Forgetting an instance which was never added drops its messages.
+        mux.forget(&0);
+        assert_eq!(mux.pending_len, 1);
+        mux.message(p, message(2));
+        assert_eq!(mux.pending[&2].len(), 1);
+    }
+
+    #[test]
+    fn test_mux_forgets_finished_instances() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let make = || keygen::<Secp256k1>(&participants, participants[0], 2, b"forget").unwrap();
+
+        let mut mux = ProtocolMux::new();
+        mux.add(0u64, make()).unwrap();
+        mux.finish(&0);
+
+        // Late messages for a finished instance are dropped, and its id can't be reused.
+        let late = frame(&0u64, b"late");
+        mux.message(participants[1], late.clone());
+        assert!(mux.pending.is_empty());
+        assert!(mux.add(0u64, make()).is_err());
+
+        mux.forget(&0);
+        assert!(mux.finished.is_empty());
+        mux.message(participants[1], late);
+        assert_eq!(mux.pending_len, 1);
+    }
+}
@@ -0,0 +1,330 @@
+//! This module contains a protocol to repair the share of a participant who lost it.
+//!
+//! The idea is that a threshold of helpers can compute the lost share
+//! by interpolating their own shares at the point of the lost participant,
+//! rather than at zero. Each helper blinds their contribution, by splitting it
+//! into random pieces summing to it, and sending one piece to every other helper.
+//! Each helper then sends the sum of the pieces they received to the lost participant,
+//! who learns only the sum of everything, i.e. their share.
+//! The helpers also send the commitment to everyone's share, which the lost participant
+//! uses to check that the share they recreated is correct.
+//!
+//! Unlike resharing, this doesn't change the shares of any other participant.
+use elliptic_curve::{Field, ScalarPrimitive};
+use zeroize::Zeroizing;
+
+use crate::{
+    compat::CSCurve,
+    participants::{ParticipantCounter, ParticipantList},
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        InitializationError, Participant, Protocol, ProtocolError, ProtocolId,
+    },
+    KeyCommitment, KeygenOutput,
+};
+
+async fn do_repair_helper<C: CSCurve>(
+    mut chan: SharedChannel,
+    helpers: ParticipantList,
+    me: Participant,
+    lost: Participant,
+    keygen_out: KeygenOutput<C>,
+) -> Result<Option<KeygenOutput<C>>, ProtocolError> {
+    let mut rng = chan.rng(b"repair share");
+
+    // Our contribution to the lost share, split into random pieces for the other helpers.
+    let mut my_piece =
+        Zeroizing::new(helpers.lagrange_at::<C>(me, lost) * keygen_out.private_share);
+    let wait0 = chan.next_waitpoint();
+    for p in helpers.others(me) {
+        let piece = Zeroizing::new(C::Scalar::random(&mut rng));
+        *my_piece -= *piece;
+        let piece: Zeroizing<ScalarPrimitive<C>> = Zeroizing::new((*piece).into());
+        chan.send_private(wait0, p, &*piece).await;
+    }
+
+    // Add up the pieces we receive, which hides the contribution of each helper.
+    let mut sum = my_piece;
+    let mut seen = ParticipantCounter::new(&helpers);
+    seen.put(me);
+    while !seen.full() {
+        let (from, piece): (_, ScalarPrimitive<C>) = chan.recv(wait0, seen.missing()).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        let piece = Zeroizing::new(piece);
+        *sum += C::Scalar::from(*piece);
+    }
+
+    let wait1 = chan.next_waitpoint();
+    let sum: Zeroizing<ScalarPrimitive<C>> = Zeroizing::new((*sum).into());
+    chan.send_private(wait1, lost, &(&*sum, &keygen_out.commitment))
+        .await;
+
+    Ok(None)
+}
+
+async fn do_repair_lost<C: CSCurve>(
+    mut chan: SharedChannel,
+    helpers: ParticipantList,
+    threshold: usize,
+    me: Participant,
+) -> Result<Option<KeygenOutput<C>>, ProtocolError> {
+    let _wait0 = chan.next_waitpoint();
+    let wait1 = chan.next_waitpoint();
+
+    let mut share = Zeroizing::new(C::Scalar::ZERO);
+    let mut commitment: Option<KeyCommitment<C>> = None;
+    let mut seen = ParticipantCounter::new(&helpers);
+    while !seen.full() {
+        let (from, (sum, their_commitment)): (_, (ScalarPrimitive<C>, KeyCommitment<C>)) =
+            chan.recv(wait1, seen.missing()).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        let sum = Zeroizing::new(sum);
+        *share += C::Scalar::from(*sum);
+        match &commitment {
+            None => commitment = Some(their_commitment),
+            Some(commitment) if *commitment != their_commitment => {
+                return Err(ProtocolError::AssertionFailed(
+                    "helpers sent different key commitments".to_string(),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Since there's at least one helper, we have a commitment.
+    let commitment = commitment.unwrap();
+    if commitment.threshold() != threshold {
+        return Err(ProtocolError::AssertionFailed(
+            "key commitment has the wrong threshold".to_string(),
+        ));
+    }
+    // The pieces hide each helper's contribution, so we can't tell who's to blame.
+    if !commitment.verify_share(me, &share) {
+        return Err(ProtocolError::AssertionFailed(
+            "repaired share does not match the key commitment".to_string(),
+        ));
+    }
+
+    Ok(Some(KeygenOutput {
+        private_share: *share,
+        public_key: commitment.public_key(),
+        commitment,
+    }))
+}
+
+async fn do_repair<C: CSCurve>(
+    chan: SharedChannel,
+    helpers: ParticipantList,
+    threshold: usize,
+    me: Participant,
+    lost: Participant,
+    keygen_out: Option<KeygenOutput<C>>,
+) -> Result<Option<KeygenOutput<C>>, ProtocolError> {
+    match keygen_out {
+        Some(keygen_out) => do_repair_helper::<C>(chan, helpers, me, lost, keygen_out).await,
+        None => do_repair_lost::<C>(chan, helpers, threshold, me).await,
+    }
+}
+
+/// The share repair protocol, recreating the share of a participant who lost it.
+///
+/// The `helpers` are participants who still have their share of the key, and
+/// there need to be at least `threshold` of them. Each of them passes the output
+/// of key generation, containing their share, and the commitment to everyone's share.
+/// The `lost` participant, who must not be one of the helpers, passes `None`.
+///
+/// The lost participant returns their output of key generation, after checking
+/// their share against the commitment the helpers agreed on, and every helper returns `None`.
+/// No other share changes, and nobody learns anything about the other shares,
+/// beyond the lost participant learning their own share.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+pub fn repair_share<C: CSCurve>(
+    helpers: &[Participant],
+    threshold: usize,
+    lost: Participant,
+    me: Participant,
+    keygen_out: Option<KeygenOutput<C>>,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = Option<KeygenOutput<C>>>, InitializationError> {
+    if helpers.len() < threshold {
+        return Err(InitializationError::BadParameters(
+            "helper count must be >= threshold".to_string(),
+        ));
+    }
+
+    let helpers = ParticipantList::new(helpers).ok_or_else(|| {
+        InitializationError::BadParameters("helper list cannot contain duplicates".to_string())
+    })?;
+
+    if helpers.contains(lost) {
+        return Err(InitializationError::BadParameters(
+            "helper list cannot contain the lost participant".to_string(),
+        ));
+    }
+
+    if me == lost && keygen_out.is_some() {
+        return Err(InitializationError::BadParameters(
+            "the lost participant cannot provide a share".to_string(),
+        ));
+    }
+
+    if me != lost && !helpers.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant must be either a helper or the lost participant".to_string(),
+        ));
+    }
+
+    if me != lost && keygen_out.is_none() {
+        return Err(InitializationError::BadParameters(
+            "helpers must provide their share".to_string(),
+        ));
+    }
+
+    if let Some(keygen_out) = &keygen_out {
+        if keygen_out.commitment.threshold() != threshold {
+            return Err(InitializationError::BadParameters(
+                "key commitment must have the same threshold".to_string(),
+            ));
+        }
+    }
+
+    let ctx = Context::new(ProtocolId::RepairShare, session_id);
+    let fut = do_repair::<C>(
+        ctx.shared_channel(),
+        helpers,
+        threshold,
+        me,
+        lost,
+        keygen_out,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::{Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        protocol::{internal::tamper_payload, run_protocol, Action, Tamper},
+        split_key,
+    };
+
+    /// Run the repair protocol, with a given helper adding one to what they send.
+    #[allow(clippy::type_complexity)]
+    fn run_repair(
+        shares: &[KeygenOutput<Secp256k1>],
+        participants: &[Participant],
+        helpers: &[Participant],
+        threshold: usize,
+        lost: Participant,
+        bad: Option<Participant>,
+    ) -> Result<Vec<(Participant, Option<KeygenOutput<Secp256k1>>)>, ProtocolError> {
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Option<KeygenOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(helpers.len() + 1);
+        for (&p, out) in participants.iter().zip(shares) {
+            let keygen_out = if p == lost {
+                None
+            } else if helpers.contains(&p) {
+                Some(out.clone())
+            } else {
+                continue;
+            };
+            let protocol =
+                repair_share::<Secp256k1>(helpers, threshold, lost, p, keygen_out, b"test")
+                    .unwrap();
+            let is_bad = Some(p) == bad;
+            let tamper = move |action: &mut Action<_>| match action {
+                Action::SendPrivate(to, m) if is_bad && *to == lost => tamper_payload(
+                    m,
+                    |(sum, commitment): (ScalarPrimitive<Secp256k1>, KeyCommitment<Secp256k1>)| {
+                        ((Scalar::from(sum) + Scalar::ONE).into(), commitment)
+                    },
+                ),
+                _ => {}
+            };
+            protocols.push((p, Box::new(Tamper { protocol, tamper })));
+        }
+        run_protocol(protocols)
+    }
+
+    #[test]
+    fn test_repair_share() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+            Participant::from(3u32),
+        ];
+        let secret = Scalar::random(&mut OsRng);
+
+        for (threshold, helpers) in [(3, vec![0, 1, 3]), (2, vec![0, 1, 3]), (2, vec![3, 0])] {
+            let shares =
+                split_key::<Secp256k1>(&mut OsRng, &secret, &participants, threshold).unwrap();
+            let lost = participants[2];
+            let helpers: Vec<_> = helpers.into_iter().map(|i| participants[i]).collect();
+
+            for (p, out) in run_repair(&shares, &participants, &helpers, threshold, lost, None)? {
+                if p == lost {
+                    let out = out.unwrap();
+                    assert_eq!(out.private_share, shares[2].private_share);
+                    assert_eq!(out.public_key, shares[2].public_key);
+                    assert_eq!(out.commitment, shares[2].commitment);
+                } else {
+                    assert!(out.is_none());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_share_checks_result() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let secret = Scalar::random(&mut OsRng);
+        let shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, 2).unwrap();
+        let lost = participants[2];
+        let helpers = &participants[..2];
+
+        let result = run_repair(&shares, &participants, helpers, 2, lost, Some(helpers[1]));
+        assert!(matches!(result, Err(ProtocolError::AssertionFailed(_))));
+    }
+
+    #[test]
+    fn test_repair_share_checks_arguments() {
+        let helpers = vec![Participant::from(0u32), Participant::from(1u32)];
+        let lost = Participant::from(2u32);
+        let shares =
+            split_key::<Secp256k1>(&mut OsRng, &Scalar::ONE, &[helpers[0], helpers[1], lost], 2)
+                .unwrap();
+        let share = Some(shares[0].clone());
+
+        let repair = |helpers: &[Participant], me, keygen_out: Option<KeygenOutput<Secp256k1>>| {
+            repair_share::<Secp256k1>(helpers, 2, lost, me, keygen_out, b"").is_ok()
+        };
+        assert!(repair(&helpers, lost, None));
+        assert!(repair(&helpers, helpers[0], share.clone()));
+        assert!(!repair(&helpers[..1], lost, None));
+        assert!(!repair(&helpers, lost, share.clone()));
+        assert!(!repair(&helpers, helpers[0], None));
+        assert!(!repair(&helpers, Participant::from(3u32), share.clone()));
+        assert!(!repair(&[helpers[0], lost], lost, None));
+        // The commitment needs to have the same threshold.
+        assert!(repair_share::<Secp256k1>(&helpers, 1, lost, helpers[0], share, b"").is_err());
+    }
+}
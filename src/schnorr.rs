@@ -0,0 +1,454 @@
+//! This module contains a threshold signing protocol for BIP340 Schnorr signatures.
+//!
+//! This uses the same key shares as ECDSA signing, so a key generated with [crate::keygen]
+//! can be used for both. The protocol follows FROST: each participant commits to
+//! two nonces, the nonce of the signature is derived from everyone's commitments
+//! and the message, and then each participant sends their share of the signature.
+//! Unlike ECDSA signing, this doesn't need any triples or presignatures.
+//!
+//! BIP340 only uses the x coordinate of points, implicitly picking the point with
+//! an even y coordinate. We handle this by negating the shares of the key, or the
+//! shares of the nonce, as needed, which every participant can do locally.
+//! The key can also be tweaked, following BIP341, for use with Taproot.
+use elliptic_curve::{
+    ops::Reduce, point::AffineCoordinates, Field, Group, PrimeField, ScalarPrimitive,
+};
+use k256::{
+    schnorr::{Signature, VerifyingKey},
+    AffinePoint, ProjectivePoint, Scalar, Secp256k1, U256,
+};
+use magikitten::Transcript;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    compat::SerializablePoint,
+    participants::{ParticipantCounter, ParticipantList, ParticipantMap},
+    protocol::{
+        internal::{make_protocol, Context},
+        InitializationError, Misbehavior, Participant, Protocol, ProtocolError, ProtocolId,
+    },
+    serde::encode,
+    KeygenOutput,
+};
+
+const LABEL: &[u8] = b"cait-sith v0.8.0 schnorr signing";
+
+const TAP_TWEAK_TAG: &[u8] = b"TapTweak";
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+/// How to tweak the key before signing, following BIP341.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaprootTweak {
+    /// Sign with the key directly, without any tweak.
+    None,
+    /// Tweak the key to commit to no scripts, as recommended by BIP86.
+    KeySpendOnly,
+    /// Tweak the key to commit to the root of a tree of scripts.
+    ScriptTree([u8; 32]),
+}
+
+/// Hash data with a tag, as defined in BIP340.
+fn tagged_hash(tag: &[u8]) -> Sha256 {
+    let tag_hash = Sha256::digest(tag);
+    Sha256::new().chain_update(tag_hash).chain_update(tag_hash)
+}
+
+/// The key actually used for signing, as an affine function of the shared key.
+///
+/// The private key used is `factor * x + offset`, where `x` is the shared private key,
+/// and `public_key` is the corresponding point, which always has an even y coordinate.
+struct SigningKey {
+    factor: Scalar,
+    offset: Scalar,
+    public_key: AffinePoint,
+}
+
+impl SigningKey {
+    fn new(public_key: &AffinePoint, tweak: &TaprootTweak) -> Option<Self> {
+        let negate_if_odd = |point: AffinePoint| {
+            if bool::from(point.y_is_odd()) {
+                -Scalar::ONE
+            } else {
+                Scalar::ONE
+            }
+        };
+
+        // BIP341 starts with the key with the same x coordinate, and an even y coordinate.
+        let mut factor = negate_if_odd(*public_key);
+        let mut offset = Scalar::ZERO;
+        let mut big_q = ProjectivePoint::from(*public_key) * factor;
+        let merkle_root: Option<&[u8]> = match tweak {
+            TaprootTweak::None => None,
+            TaprootTweak::KeySpendOnly => Some(&[]),
+            TaprootTweak::ScriptTree(root) => Some(root),
+        };
+        if let Some(merkle_root) = merkle_root {
+            let t = tagged_hash(TAP_TWEAK_TAG)
+                .chain_update(public_key.x())
+                .chain_update(merkle_root)
+                .finalize();
+            offset = Option::from(Scalar::from_repr(t))?;
+            big_q += ProjectivePoint::GENERATOR * offset;
+        }
+        if big_q.is_identity().into() {
+            return None;
+        }
+
+        // BIP340 then signs with the key with an even y coordinate, once again.
+        let sign = negate_if_odd(big_q.into());
+        factor *= sign;
+        offset *= sign;
+        big_q *= sign;
+        Some(Self {
+            factor,
+            offset,
+            public_key: big_q.into(),
+        })
+    }
+}
+
+/// The BIP340 key signatures will be valid for, given the shared public key and a tweak.
+///
+/// This returns `None` if the tweak is invalid, which happens with negligible probability.
+pub fn schnorr_verifying_key(
+    public_key: &AffinePoint,
+    tweak: &TaprootTweak,
+) -> Option<VerifyingKey> {
+    let key = SigningKey::new(public_key, tweak)?;
+    VerifyingKey::from_bytes(&key.public_key.x()).ok()
+}
+
+async fn do_sign_schnorr(
+    ctx: Context<'_>,
+    participants: ParticipantList,
+    me: Participant,
+    keygen_out: KeygenOutput<Secp256k1>,
+    key: SigningKey,
+    msg: Vec<u8>,
+) -> Result<Signature, ProtocolError> {
+    let mut chan = ctx.shared_channel();
+    let mut rng = chan.rng(b"schnorr nonces");
+
+    // Spec 1.1
+    let d_i = Zeroizing::new(Scalar::random(&mut rng));
+    let e_i = Zeroizing::new(Scalar::random(&mut rng));
+
+    // Spec 1.2
+    let big_d_i = ProjectivePoint::GENERATOR * *d_i;
+    let big_e_i = ProjectivePoint::GENERATOR * *e_i;
+
+    // Spec 1.3
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(
+        wait0,
+        &(
+            SerializablePoint::<Secp256k1>::from_projective(&big_d_i),
+            SerializablePoint::<Secp256k1>::from_projective(&big_e_i),
+        ),
+    )
+    .await;
+
+    // Spec 2.1
+    let mut all_nonces = ParticipantMap::new(&participants);
+    all_nonces.put(
+        me,
+        (
+            SerializablePoint::<Secp256k1>::from_projective(&big_d_i),
+            SerializablePoint::<Secp256k1>::from_projective(&big_e_i),
+        ),
+    );
+    while !all_nonces.full() {
+        let (from, nonces) = chan.recv(wait0, all_nonces.missing()).await?;
+        all_nonces.put(from, nonces);
+    }
+
+    // Spec 2.2
+    let mut transcript = Transcript::new(LABEL);
+    transcript.message(b"session id", ctx.session_id());
+    transcript.message(b"participants", &encode(&participants));
+    transcript.message(b"public key", &key.public_key.x());
+    transcript.message(b"message", &msg);
+    transcript.message(b"nonces", &encode(&all_nonces));
+
+    // Spec 2.3
+    let mut all_big_r = ParticipantMap::new(&participants);
+    let mut big_r = ProjectivePoint::IDENTITY;
+    for p in participants.others(me).chain(std::iter::once(me)) {
+        let rho = Scalar::random(
+            &mut transcript
+                .forked(b"binding", &p.bytes())
+                .challenge(b"binding factor"),
+        );
+        let (big_d, big_e) = &all_nonces[p];
+        let big_r_p = big_d.to_projective() + big_e.to_projective() * rho;
+        big_r += big_r_p;
+        all_big_r.put(p, (big_r_p, rho));
+    }
+    if big_r.is_identity().into() {
+        return Err(ProtocolError::AssertionFailed(
+            "nonce commitment is the identity".to_string(),
+        ));
+    }
+
+    // Spec 2.4
+    let big_r = big_r.to_affine();
+    let nonce_sign = if bool::from(big_r.y_is_odd()) {
+        -Scalar::ONE
+    } else {
+        Scalar::ONE
+    };
+
+    // Spec 2.5
+    let c = <Scalar as Reduce<U256>>::reduce_bytes(
+        &tagged_hash(CHALLENGE_TAG)
+            .chain_update(big_r.x())
+            .chain_update(key.public_key.x())
+            .chain_update(&msg)
+            .finalize(),
+    );
+
+    // Spec 2.6
+    let rho_i = all_big_r[me].1;
+    let lambda_i = participants.lagrange::<Secp256k1>(me);
+    let key_i = Zeroizing::new(key.factor * keygen_out.private_share + key.offset);
+    let z_i = nonce_sign * (*d_i + rho_i * *e_i) + c * lambda_i * *key_i;
+
+    // Spec 2.7
+    let wait1 = chan.next_waitpoint();
+    {
+        let z_i: ScalarPrimitive<Secp256k1> = z_i.into();
+        chan.send_many(wait1, &z_i).await;
+    }
+
+    // Spec 3.1 + 3.2
+    let mut seen = ParticipantCounter::new(&participants);
+    let mut s = z_i;
+    seen.put(me);
+    while !seen.full() {
+        let (from, z_j): (_, ScalarPrimitive<Secp256k1>) = chan.recv(wait1, seen.missing()).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        let z_j = Scalar::from(z_j);
+        // The share of the signature should match the commitments to the share of the key.
+        let big_x_j = ProjectivePoint::from(keygen_out.commitment.public_share(from));
+        let big_key_j = big_x_j * key.factor + ProjectivePoint::GENERATOR * key.offset;
+        let lambda_j = participants.lagrange::<Secp256k1>(from);
+        if ProjectivePoint::GENERATOR * z_j
+            != all_big_r[from].0 * nonce_sign + big_key_j * (c * lambda_j)
+        {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadPublicShare,
+            });
+        }
+        s += z_j;
+    }
+
+    // Spec 3.3
+    let mut bytes = [0u8; Signature::BYTE_SIZE];
+    bytes[..32].copy_from_slice(&big_r.x());
+    bytes[32..].copy_from_slice(&s.to_repr());
+    let sig = Signature::try_from(&bytes[..]).map_err(|_| {
+        ProtocolError::AssertionFailed("signature has a zero component".to_string())
+    })?;
+    let verifying_key = VerifyingKey::from_bytes(&key.public_key.x())
+        .map_err(|_| ProtocolError::AssertionFailed("invalid public key".to_string()))?;
+    if verifying_key.verify_raw(&msg, &sig).is_err() {
+        return Err(ProtocolError::AssertionFailed(
+            "signature failed to verify".to_string(),
+        ));
+    }
+
+    // Spec 3.4
+    Ok(sig)
+}
+
+/// The threshold BIP340 Schnorr signing protocol, for `Secp256k1`.
+///
+/// This uses the shares of a key generated with [crate::keygen], and doesn't need
+/// any triples or presignatures. At least a threshold of participants need to be present.
+/// The resulting signature is valid for the key returned by [schnorr_verifying_key],
+/// given the same `tweak`. With [TaprootTweak::None], this is the x coordinate of the
+/// public key in `keygen_out`.
+///
+/// The `msg` is signed as is, as in BIP340, so it should usually be the hash of a message,
+/// e.g. a Taproot signature hash.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+pub fn sign_schnorr(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: KeygenOutput<Secp256k1>,
+    msg: &[u8],
+    tweak: TaprootTweak,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = Signature>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    if participants.len() < keygen_out.commitment.threshold() {
+        return Err(InitializationError::BadParameters(
+            "participant count must be >= threshold".to_string(),
+        ));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let key = SigningKey::new(&keygen_out.public_key, &tweak)
+        .ok_or_else(|| InitializationError::BadParameters("invalid taproot tweak".to_string()))?;
+
+    let ctx = Context::new(ProtocolId::SignSchnorr, session_id);
+    let fut = do_sign_schnorr(ctx.clone(), participants, me, keygen_out, key, msg.to_vec());
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::schnorr::SigningKey as SchnorrSigningKey;
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        protocol::{run_protocol, Action, Corrupt},
+        split_key,
+    };
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_sign(
+        participants: &[Participant],
+        shares: &[KeygenOutput<Secp256k1>],
+        msg: &[u8],
+        tweak: TaprootTweak,
+        bad: Option<Participant>,
+    ) -> Result<Vec<(Participant, Signature)>, ProtocolError> {
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Signature>>)> =
+            Vec::with_capacity(participants.len());
+        for (&p, keygen_out) in participants.iter().zip(shares) {
+            let protocol = sign_schnorr(
+                participants,
+                p,
+                keygen_out.clone(),
+                msg,
+                tweak,
+                b"test schnorr",
+            )
+            .unwrap();
+            // Only corrupt the second broadcast, containing the share of the signature.
+            let mut sent = 0;
+            let should_corrupt = move |action: &Action<_>| {
+                if !matches!(action, Action::SendMany(_)) {
+                    return false;
+                }
+                sent += 1;
+                Some(p) == bad && sent == 2
+            };
+            protocols.push((
+                p,
+                Box::new(Corrupt {
+                    protocol,
+                    should_corrupt,
+                }),
+            ));
+        }
+        run_protocol(protocols)
+    }
+
+    #[test]
+    fn test_sign_schnorr() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let secret = Scalar::random(&mut OsRng);
+        let shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, 2).unwrap();
+        let msg = [7u8; 32];
+
+        for tweak in [
+            TaprootTweak::None,
+            TaprootTweak::KeySpendOnly,
+            TaprootTweak::ScriptTree([3u8; 32]),
+        ] {
+            let verifying_key = schnorr_verifying_key(&shares[0].public_key, &tweak).unwrap();
+            // Only a threshold of participants is needed.
+            let result = run_sign(&participants[1..], &shares[1..], &msg, tweak, None)?;
+            for (_, sig) in &result {
+                assert_eq!(*sig, result[0].1);
+                assert!(verifying_key.verify_raw(&msg, sig).is_ok());
+            }
+        }
+
+        // Without a tweak, the key is the shared key itself.
+        let signing_key = SchnorrSigningKey::from_bytes(&secret.to_repr())?;
+        assert_eq!(
+            schnorr_verifying_key(&shares[0].public_key, &TaprootTweak::None),
+            Some(*signing_key.verifying_key())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_taproot_tweak_vector() {
+        // Taken from the test vectors in BIP86, for the first receiving address.
+        let internal_key =
+            decode_hex("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115");
+        let output_key =
+            decode_hex("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+
+        let internal_key = *VerifyingKey::from_bytes(&internal_key).unwrap().as_affine();
+        let tweaked = schnorr_verifying_key(&internal_key, &TaprootTweak::KeySpendOnly).unwrap();
+        assert_eq!(tweaked.to_bytes()[..], output_key[..]);
+    }
+
+    #[test]
+    fn test_sign_schnorr_blames_bad_share() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let secret = Scalar::random(&mut OsRng);
+        let shares = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, 2).unwrap();
+        let cheater = participants[2];
+
+        match run_sign(
+            &participants,
+            &shares,
+            b"hello",
+            TaprootTweak::KeySpendOnly,
+            Some(cheater),
+        ) {
+            Err(ProtocolError::Misbehaved {
+                participant,
+                reason,
+            }) => {
+                assert_eq!(participant, cheater);
+                assert_eq!(reason, Misbehavior::BadPublicShare);
+            }
+            _ => panic!("expected the cheater to be blamed"),
+        }
+    }
+}
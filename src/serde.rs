@@ -1,9 +1,74 @@
 use std::io::Write;
 
 use crate::compat::{CSCurve, SerializablePoint};
+use bincode::Options;
 use ecdsa::elliptic_curve::ScalarPrimitive;
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 
+/// The encodings the messages of a protocol can use.
+///
+/// Every participant in a protocol needs to use the same codec, which is checked
+/// as part of the envelope of each message. This only affects the messages which
+/// are sent; the values hashed into transcripts are always encoded the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Codec {
+    /// MessagePack, with rmp-serde.
+    ///
+    /// This is the default, and tends to be more compact, since integers have a variable length.
+    #[default]
+    MessagePack = 1,
+    /// A strict binary encoding, where every value of a given type has a predictable size.
+    ///
+    /// This is bincode, with fixed size little endian integers, and lengths encoded
+    /// as 8 byte integers. Scalars are encoded as their fixed size representation,
+    /// and points in compressed SEC1 form, prefixed with their length.
+    ///
+    /// When decoding, anything but the exact encoding of a value is rejected,
+    /// like trailing bytes, scalars which aren't reduced, or points which aren't compressed.
+    Canonical = 2,
+}
+
+impl Codec {
+    fn canonical_options() -> impl Options + Copy {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .with_little_endian()
+            .reject_trailing_bytes()
+    }
+
+    /// Encode a value, after a tag.
+    pub(crate) fn encode_with_tag<T: Serialize>(&self, tag: &[u8], val: &T) -> Vec<u8> {
+        match self {
+            Codec::MessagePack => encode_with_tag(tag, val),
+            Codec::Canonical => {
+                let mut out = Vec::with_capacity(128);
+                out.extend_from_slice(tag);
+                Self::canonical_options()
+                    .serialize_into(&mut out, val)
+                    .expect("failed to encode value");
+                out
+            }
+        }
+    }
+
+    /// Decode a value, returning `None` if the input isn't a valid encoding.
+    pub(crate) fn decode<T: Serialize + DeserializeOwned>(&self, input: &[u8]) -> Option<T> {
+        match self {
+            Codec::MessagePack => decode(input).ok(),
+            Codec::Canonical => {
+                let options = Self::canonical_options();
+                let out: T = options.deserialize(input).ok()?;
+                // Some types accept more than one encoding of the same value, so the
+                // only way to know that the input was canonical is to encode it again.
+                if options.serialize(&out).ok()? != input {
+                    return None;
+                }
+                Some(out)
+            }
+        }
+    }
+}
+
 /// Encode an arbitrary serializable value into a vec.
 pub fn encode<T: Serialize>(val: &T) -> Vec<u8> {
     rmp_serde::encode::to_vec(val).expect("failed to encode value")
@@ -51,6 +116,23 @@ pub fn serialize_projective_point<C: CSCurve, S: Serializer>(
     SerializablePoint::<C>::from_projective(data).serialize(serializer)
 }
 
+/// Serialize a single affine point.
+pub fn serialize_affine_point<C: CSCurve, S: Serializer>(
+    data: &C::AffinePoint,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    C::serialize_point(data, serializer)
+}
+
+/// Deserialize a single affine point.
+pub fn deserialize_affine_point<'de, C, D>(deserializer: D) -> Result<C::AffinePoint, D::Error>
+where
+    C: CSCurve,
+    D: Deserializer<'de>,
+{
+    C::deserialize_point(deserializer)
+}
+
 /// Serialize an arbitrary scalar.
 pub fn serialize_scalar<C: CSCurve, S: Serializer>(
     data: &C::Scalar,
@@ -74,3 +156,148 @@ where
 pub fn decode<T: DeserializeOwned>(input: &[u8]) -> Result<T, rmp_serde::decode::Error> {
     rmp_serde::decode::from_slice(input)
 }
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::{sec1::ToEncodedPoint, Field};
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        protocol::Participant,
+        split_key,
+        triples::{self, TriplePub, TripleShare},
+        FullSignature, PresignCommitments, PresignOutput, PresignShareCommitment,
+    };
+
+    fn round_trip<T: Serialize + DeserializeOwned>(val: &T) -> T {
+        decode(&encode(val)).unwrap()
+    }
+
+    #[test]
+    fn test_canonical_codec() {
+        let codec = Codec::Canonical;
+        let x = Scalar::random(&mut OsRng);
+        let big_x =
+            SerializablePoint::<Secp256k1>::from_projective(&(ProjectivePoint::GENERATOR * x));
+        let x: ScalarPrimitive<Secp256k1> = x.into();
+
+        // Scalars and points always have the same size.
+        let encoded_x = codec.encode_with_tag(&[], &x);
+        let encoded_big_x = codec.encode_with_tag(&[], &big_x);
+        assert_eq!(encoded_x.len(), 32);
+        assert_eq!(encoded_big_x.len(), 8 + 33);
+        assert_eq!(
+            codec.decode::<ScalarPrimitive<Secp256k1>>(&encoded_x),
+            Some(x)
+        );
+        assert_eq!(
+            codec
+                .decode::<SerializablePoint<Secp256k1>>(&encoded_big_x)
+                .map(|p| p.to_projective()),
+            Some(big_x.to_projective())
+        );
+
+        // Trailing bytes are rejected.
+        let mut longer = encoded_x.clone();
+        longer.push(0);
+        assert!(codec
+            .decode::<ScalarPrimitive<Secp256k1>>(&longer)
+            .is_none());
+        // So are scalars which aren't reduced.
+        assert!(codec
+            .decode::<ScalarPrimitive<Secp256k1>>(&[0xFF; 32])
+            .is_none());
+        // And points which aren't compressed.
+        let uncompressed = big_x.to_projective().to_affine().to_encoded_point(false);
+        let mut encoded = (uncompressed.len() as u64).to_le_bytes().to_vec();
+        encoded.extend_from_slice(uncompressed.as_bytes());
+        assert!(codec
+            .decode::<SerializablePoint<Secp256k1>>(&encoded)
+            .is_none());
+        // MessagePack isn't as strict, but the codecs can't read each other.
+        assert!(Codec::MessagePack
+            .decode::<ScalarPrimitive<Secp256k1>>(&encoded_x)
+            .is_none());
+    }
+
+    #[test]
+    fn test_participant_round_trip() {
+        let p = Participant::from(7u32);
+        assert_eq!(round_trip(&p), p);
+    }
+
+    #[test]
+    fn test_keygen_output_round_trip() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let secret = Scalar::random(&mut OsRng);
+        let out = split_key::<Secp256k1>(&mut OsRng, &secret, &participants, 2)
+            .unwrap()
+            .remove(0);
+        let decoded = round_trip(&out);
+        assert_eq!(decoded.private_share, out.private_share);
+        assert_eq!(decoded.public_key, out.public_key);
+        assert_eq!(decoded.commitment, out.commitment);
+    }
+
+    #[test]
+    fn test_triple_round_trip() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let (triple_pub, triple_shares): (TriplePub<Secp256k1>, Vec<TripleShare<Secp256k1>>) =
+            triples::deal(&mut OsRng, &participants, 2);
+
+        assert_eq!(round_trip(&triple_pub), triple_pub);
+        for share in &triple_shares {
+            let decoded = round_trip(share);
+            assert_eq!(decoded.a, share.a);
+            assert_eq!(decoded.b, share.b);
+            assert_eq!(decoded.c, share.c);
+        }
+    }
+
+    #[test]
+    fn test_presign_output_round_trip() {
+        let out = PresignOutput::<Secp256k1> {
+            big_r: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+            k: Scalar::random(&mut OsRng),
+            sigma: Scalar::random(&mut OsRng),
+            commitments: Some(PresignCommitments {
+                big_k: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+                big_sigma: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+                shares: vec![PresignShareCommitment {
+                    participant: Participant::from(1u32),
+                    big_k: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+                    big_sigma: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+                }],
+            }),
+        };
+        let decoded = round_trip(&out);
+        assert_eq!(decoded.big_r, out.big_r);
+        assert_eq!(decoded.k, out.k);
+        assert_eq!(decoded.sigma, out.sigma);
+        let (decoded, expected) = (
+            decoded.commitments.as_ref().unwrap(),
+            out.commitments.as_ref().unwrap(),
+        );
+        assert_eq!(decoded.big_k, expected.big_k);
+        assert_eq!(decoded.big_sigma, expected.big_sigma);
+        assert_eq!(
+            decoded.shares[0].participant,
+            expected.shares[0].participant
+        );
+        assert_eq!(decoded.shares[0].big_k, expected.shares[0].big_k);
+        assert_eq!(decoded.shares[0].big_sigma, expected.shares[0].big_sigma);
+    }
+
+    #[test]
+    fn test_full_signature_round_trip() {
+        let sig = FullSignature::<Secp256k1> {
+            big_r: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+            s: Scalar::random(&mut OsRng),
+        };
+        let decoded = round_trip(&sig);
+        assert_eq!(decoded.big_r, sig.big_r);
+        assert_eq!(decoded.s, sig.s);
+    }
+}
@@ -1,12 +1,34 @@
-use elliptic_curve::{ops::Invert, scalar::IsHigh, Field, Group, ScalarPrimitive};
-use subtle::ConditionallySelectable;
+use std::{iter, ops::Add};
+
+use digest::Digest;
+use ecdsa::{
+    hazmat::{bits2field, DigestPrimitive},
+    RecoveryId, Signature, SignatureSize,
+};
+// generic-array 0.14 deprecates ArrayLength, but it's what ecdsa uses in its bounds.
+#[allow(deprecated)]
+use elliptic_curve::generic_array::ArrayLength;
+use elliptic_curve::{
+    ops::{Invert, Reduce},
+    point::AffineCoordinates,
+    scalar::IsHigh,
+    Field, FieldBytesSize, Group, PrimeField, ScalarPrimitive,
+};
+use magikitten::Transcript;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    compat::{self, CSCurve},
-    participants::{ParticipantCounter, ParticipantList},
+    compat::{self, CSCurve, SerializablePoint},
+    participants::{ParticipantCounter, ParticipantList, ParticipantMap},
+    presign::{PresignCommitments, PresignShareCommitment},
     protocol::{
         internal::{make_protocol, Context, SharedChannel},
-        InitializationError, Participant, Protocol, ProtocolError,
+        InitializationError, Misbehavior, Participant, Protocol, ProtocolError, ProtocolId,
+    },
+    serde::{
+        deserialize_affine_point, deserialize_scalar, encode, serialize_affine_point,
+        serialize_scalar,
     },
     PresignOutput,
 };
@@ -22,18 +44,98 @@ use crate::{
 ///
 /// To support these variants, this simply gives you a normal signature, along with the entire
 /// first point.
-#[derive(Clone)]
+///
+/// Methods are provided to convert this into the formats most commonly used,
+/// like a plain [ecdsa::Signature], or a [RecoveryId] to go along with it.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FullSignature<C: CSCurve> {
     /// This is the entire first point.
+    ///
+    /// This point satisfies `s * big_r = Hash(m) * G + r * X`, with `X` the public key.
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
     pub big_r: C::AffinePoint,
     /// This is the second scalar, normalized to be in the lower range.
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub s: C::Scalar,
 }
 
 impl<C: CSCurve> FullSignature<C> {
+    /// Create a signature from the first point and the second scalar, normalizing s.
+    ///
+    /// If s is in the upper range, we negate both s and the first point,
+    /// which keeps the signature valid, and the first point consistent with s.
+    fn normalized(big_r: C::AffinePoint, s: C::Scalar) -> Self {
+        if s.is_high().into() {
+            Self {
+                big_r: (-C::ProjectivePoint::from(big_r)).into(),
+                s: -s,
+            }
+        } else {
+            Self { big_r, s }
+        }
+    }
+
+    /// The first scalar of the signature, derived from the x coordinate of the first point.
+    pub fn r(&self) -> C::Scalar {
+        compat::x_coordinate::<C>(&self.big_r)
+    }
+
+    /// The recovery id of this signature.
+    ///
+    /// This records the parity of the y coordinate of the first point,
+    /// and whether or not its x coordinate overflowed the order of the curve,
+    /// which is enough to recover the public key from the signature.
+    pub fn recovery_id(&self) -> RecoveryId {
+        let is_y_odd = self.big_r.y_is_odd().into();
+        let is_x_reduced = self.big_r.x() != self.r().to_repr();
+        RecoveryId::new(is_y_odd, is_x_reduced)
+    }
+
+    /// Convert this into a standard ECDSA signature.
+    ///
+    /// This will fail if either scalar is zero.
+    #[allow(deprecated)]
+    pub fn to_signature(&self) -> Result<Signature<C>, ecdsa::Error>
+    where
+        SignatureSize<C>: ArrayLength<u8>,
+    {
+        Signature::from_scalars(self.r(), self.s)
+    }
+
+    /// Encode this signature in the compact format, as `r || s`.
+    #[allow(deprecated)]
+    pub fn to_bytes(&self) -> Result<ecdsa::SignatureBytes<C>, ecdsa::Error>
+    where
+        SignatureSize<C>: ArrayLength<u8>,
+    {
+        Ok(self.to_signature()?.to_bytes())
+    }
+
+    /// Encode this signature with ASN.1 DER.
+    #[allow(deprecated)]
+    pub fn to_der(&self) -> Result<ecdsa::der::Signature<C>, ecdsa::Error>
+    where
+        SignatureSize<C>: ArrayLength<u8>,
+        ecdsa::der::MaxSize<C>: ArrayLength<u8>,
+        <FieldBytesSize<C> as Add>::Output: Add<ecdsa::der::MaxOverhead> + ArrayLength<u8>,
+    {
+        Ok(self.to_signature()?.to_der())
+    }
+
+    /// Verify this signature, for a given public key, and the hash of a message.
+    ///
+    /// This is the same check performed at the end of the signing protocol.
+    ///
+    /// **WARNING** The hash needs to come from an actual message, see [sign].
     #[must_use]
-    fn verify(&self, public_key: &C::AffinePoint, msg_hash: &C::Scalar) -> bool {
-        let r: C::Scalar = compat::x_coordinate::<C>(&self.big_r);
+    pub fn verify(&self, public_key: &C::AffinePoint, msg_hash: &C::Scalar) -> bool {
+        let r = self.r();
         if r.is_zero().into() || self.s.is_zero().into() {
             return false;
         }
@@ -42,6 +144,217 @@ impl<C: CSCurve> FullSignature<C> {
             + (C::ProjectivePoint::from(*public_key) * (r * s_inv));
         compat::x_coordinate::<C>(&reproduced.into()) == r
     }
+
+    /// Verify this signature, for a given public key, and a digest of the message.
+    ///
+    /// The digest should already have been updated with the message.
+    #[must_use]
+    pub fn verify_digest<D: Digest>(&self, public_key: &C::AffinePoint, digest: D) -> bool {
+        match bits2field::<C>(&digest.finalize()) {
+            Ok(bytes) => {
+                let msg_hash = <C::Scalar as Reduce<C::Uint>>::reduce_bytes(&bytes);
+                self.verify(public_key, &msg_hash)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Verify this signature, for a given public key, and a message.
+    ///
+    /// The message gets hashed with the digest usually used with this curve.
+    #[must_use]
+    pub fn verify_message(&self, public_key: &C::AffinePoint, msg: &[u8]) -> bool
+    where
+        C: DigestPrimitive,
+    {
+        self.verify_digest(public_key, C::Digest::new_with_prefix(msg))
+    }
+
+    /// Verify many signatures, each with a public key and the hash of a message.
+    ///
+    /// This returns true only if every signature is valid, like calling [Self::verify]
+    /// on each of them would.
+    ///
+    /// Because we have the entire first point of each signature, we can first check
+    /// a random linear combination of `s * big_r = msg_hash * G + r * public_key`
+    /// for every signature at once, which needs only one multiplication by the generator.
+    /// Only if this fails do we fall back to checking each signature on its own.
+    #[must_use]
+    pub fn verify_batch(
+        rng: &mut impl CryptoRngCore,
+        items: &[(C::AffinePoint, C::Scalar, FullSignature<C>)],
+    ) -> bool {
+        let mut generator_coefficient = C::Scalar::ZERO;
+        let mut acc = C::ProjectivePoint::identity();
+        for (public_key, msg_hash, sig) in items {
+            let r = sig.r();
+            if r.is_zero().into() || sig.s.is_zero().into() {
+                return false;
+            }
+            let c = C::sample_scalar_constant_time(rng);
+            generator_coefficient += c * msg_hash;
+            acc += C::ProjectivePoint::from(sig.big_r) * (c * sig.s)
+                - C::ProjectivePoint::from(*public_key) * (c * r);
+        }
+        acc -= C::ProjectivePoint::generator() * generator_coefficient;
+        if acc.is_identity().into() {
+            return true;
+        }
+        items
+            .iter()
+            .all(|(public_key, msg_hash, sig)| sig.verify(public_key, msg_hash))
+    }
+}
+
+/// A signature in the `(r, s, v)` format used by Ethereum.
+#[cfg(any(feature = "k256", test))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EthereumSignature {
+    /// The first scalar, as big-endian bytes.
+    pub r: [u8; 32],
+    /// The second scalar, as big-endian bytes.
+    pub s: [u8; 32],
+    /// The recovery id, offset by 27.
+    pub v: u8,
+}
+
+#[cfg(any(feature = "k256", test))]
+impl EthereumSignature {
+    /// Encode this signature as `r || s || v`.
+    pub fn to_bytes(self) -> [u8; 65] {
+        let mut out = [0u8; 65];
+        out[..32].copy_from_slice(&self.r);
+        out[32..64].copy_from_slice(&self.s);
+        out[64] = self.v;
+        out
+    }
+}
+
+#[cfg(any(feature = "k256", test))]
+impl FullSignature<k256::Secp256k1> {
+    /// Convert this into the `(r, s, v)` format used by Ethereum.
+    ///
+    /// This will fail if the signature is invalid, or in the negligibly rare
+    /// case where the x coordinate of the first point overflowed the order of the curve,
+    /// since Ethereum can't represent this.
+    pub fn to_ethereum(&self) -> Result<EthereumSignature, ecdsa::Error> {
+        let recovery_id = self.recovery_id();
+        if recovery_id.is_x_reduced() {
+            return Err(ecdsa::Error::new());
+        }
+        let (r, s) = self.to_signature()?.split_bytes();
+        Ok(EthereumSignature {
+            r: r.into(),
+            s: s.into(),
+            v: 27 + recovery_id.to_byte(),
+        })
+    }
+}
+
+/// Try and find a participant whose signature share doesn't match their commitment.
+///
+/// This returns an error blaming that participant, if one was found.
+fn blame_signature_share<C: CSCurve>(
+    participants: &ParticipantList,
+    me: Participant,
+    commitments: &PresignCommitments<C>,
+    all_s: &ParticipantMap<'_, C::Scalar>,
+    r: C::Scalar,
+    msg_hash: C::Scalar,
+) -> Result<(), ProtocolError> {
+    let lookup = |p: Participant| commitments.shares.iter().find(|c| c.participant == p);
+    let mut big_k = C::ProjectivePoint::identity();
+    let mut big_sigma = C::ProjectivePoint::identity();
+    for p in participants.others(me).chain(iter::once(me)) {
+        let share = lookup(p).ok_or_else(|| {
+            ProtocolError::AssertionFailed(format!("no commitment to the share of {:?}", p))
+        })?;
+        let lambda = participants.lagrange::<C>(p);
+        big_k += C::ProjectivePoint::from(share.big_k) * lambda;
+        big_sigma += C::ProjectivePoint::from(share.big_sigma) * lambda;
+    }
+    // If the commitments are inconsistent, we can't trust them to blame anyone.
+    if big_k != commitments.big_k.into() || big_sigma != commitments.big_sigma.into() {
+        return Err(ProtocolError::AssertionFailed(
+            "presignature commitments are inconsistent".to_string(),
+        ));
+    }
+    for p in participants.others(me) {
+        // We've checked that the lookup succeeds just above.
+        let share = lookup(p).unwrap();
+        let lambda = participants.lagrange::<C>(p);
+        let expected = (C::ProjectivePoint::from(share.big_k) * msg_hash
+            + C::ProjectivePoint::from(share.big_sigma) * r)
+            * lambda;
+        if C::ProjectivePoint::generator() * all_s[p] != expected {
+            return Err(ProtocolError::Misbehaved {
+                participant: p,
+                reason: Misbehavior::BadPublicShare,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The label we use for hashing the arguments to rerandomize a presignature.
+const RERANDOMIZE_LABEL: &[u8] = b"cait-sith v0.8.0 rerandomize presignature";
+
+/// Rerandomize a presignature, binding it to a message, and a tweak of the key.
+///
+/// This multiplies the nonce point by a public value delta, derived by hashing all
+/// of the public arguments, and divides the shares of k and sigma by delta,
+/// after adjusting sigma to account for the tweak.
+fn rerandomize_presignature<C: CSCurve>(
+    participants: &ParticipantList,
+    public_key: &C::AffinePoint,
+    presignature: &PresignOutput<C>,
+    msg_hash: &C::Scalar,
+    tweak: &C::Scalar,
+) -> Option<PresignOutput<C>> {
+    let mut transcript = Transcript::new(RERANDOMIZE_LABEL);
+    transcript.message(b"group", C::NAME);
+    transcript.message(b"participants", &encode(participants));
+    transcript.message(
+        b"public key",
+        &encode(&SerializablePoint::<C>::from_projective(
+            &(*public_key).into(),
+        )),
+    );
+    transcript.message(
+        b"big_r",
+        &encode(&SerializablePoint::<C>::from_projective(
+            &presignature.big_r.into(),
+        )),
+    );
+    transcript.message(b"msg_hash", &msg_hash.to_repr());
+    transcript.message(b"tweak", &tweak.to_repr());
+    let delta = C::Scalar::random(&mut transcript.challenge(b"delta"));
+    let delta_inv: C::Scalar = Option::from(Field::invert(&delta))?;
+
+    let scale = |point: C::AffinePoint| (C::ProjectivePoint::from(point) * delta_inv).into();
+    let tweaked = presignature.with_tweak(tweak);
+    let commitments = tweaked
+        .commitments
+        .as_ref()
+        .map(|commitments| PresignCommitments {
+            big_k: scale(commitments.big_k),
+            big_sigma: scale(commitments.big_sigma),
+            shares: commitments
+                .shares
+                .iter()
+                .map(|share| PresignShareCommitment {
+                    participant: share.participant,
+                    big_k: scale(share.big_k),
+                    big_sigma: scale(share.big_sigma),
+                })
+                .collect(),
+        });
+    Some(PresignOutput {
+        big_r: (C::ProjectivePoint::from(presignature.big_r) * delta).into(),
+        k: tweaked.k * delta_inv,
+        sigma: tweaked.sigma * delta_inv,
+        commitments,
+    })
 }
 
 async fn do_sign<C: CSCurve>(
@@ -72,24 +385,25 @@ async fn do_sign<C: CSCurve>(
 
     // Spec 2.1 + 2.2
     let mut seen = ParticipantCounter::new(&participants);
+    let mut all_s = ParticipantMap::new(&participants);
     let mut s: C::Scalar = s_i;
     seen.put(me);
     while !seen.full() {
-        let (from, s_j): (_, ScalarPrimitive<C>) = chan.recv(wait0).await?;
+        let (from, s_j): (_, ScalarPrimitive<C>) = chan.recv(wait0, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
-        s += C::Scalar::from(s_j)
+        s += C::Scalar::from(s_j);
+        all_s.put(from, C::Scalar::from(s_j));
     }
 
     // Spec 2.3
-    // Optionally, normalize s
-    s.conditional_assign(&(-s), s.is_high());
-    let sig = FullSignature {
-        big_r: presignature.big_r,
-        s,
-    };
+    let sig = FullSignature::normalized(presignature.big_r, s);
     if !sig.verify(&public_key, &msg_hash) {
+        // Spec B.6
+        if let Some(commitments) = &presignature.commitments {
+            blame_signature_share(&participants, me, commitments, &all_s, r, msg_hash)?;
+        }
         return Err(ProtocolError::AssertionFailed(
             "signature failed to verify".to_string(),
         ));
@@ -104,12 +418,16 @@ async fn do_sign<C: CSCurve>(
 /// **WARNING** You must absolutely hash an actual message before passing it to
 /// this function. Allowing the signing of arbitrary scalars *is* a security risk,
 /// and this function only tolerates this risk to allow for genericity.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
 pub fn sign<C: CSCurve>(
     participants: &[Participant],
     me: Participant,
     public_key: C::AffinePoint,
     presignature: PresignOutput<C>,
     msg_hash: C::Scalar,
+    session_id: &[u8],
 ) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
@@ -122,7 +440,7 @@ pub fn sign<C: CSCurve>(
         InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
     })?;
 
-    let ctx = Context::new();
+    let ctx = Context::new(ProtocolId::Sign, session_id);
     let fut = do_sign(
         ctx.shared_channel(),
         participants,
@@ -134,18 +452,74 @@ pub fn sign<C: CSCurve>(
     Ok(make_protocol(ctx, fut))
 }
 
+/// The signature protocol, rerandomizing the presignature with the message first.
+///
+/// Like [sign], but before signing, the presignature is rerandomized by hashing
+/// the public key, the presignature, the message hash, the tweak, and the participants.
+/// This binds the nonce used in the signature to the message being signed,
+/// which protects against attacks where the message, or the tweak of the key,
+/// is chosen after the presignature is known.
+///
+/// The `public_key` is the original key, used to create the presignature,
+/// and the `tweak` is added to this key, e.g. one from [crate::derive_key],
+/// or zero, to sign with the original key.
+/// The resulting signature is valid for `public_key + tweak * G`.
+///
+/// All participants need to agree on whether or not to rerandomize.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_rerandomized<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    tweak: C::Scalar,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
+    let participant_list = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+    let presignature = rerandomize_presignature(
+        &participant_list,
+        &public_key,
+        &presignature,
+        &msg_hash,
+        &tweak,
+    )
+    .ok_or_else(|| {
+        InitializationError::BadParameters("failed to rerandomize presignature".to_string())
+    })?;
+    let public_key =
+        (C::ProjectivePoint::from(public_key) + C::ProjectivePoint::generator() * tweak).into();
+    sign(
+        participants,
+        me,
+        public_key,
+        presignature,
+        msg_hash,
+        session_id,
+    )
+}
+
 #[cfg(test)]
 mod test {
     use std::error::Error;
 
     use ecdsa::Signature;
     use k256::{
-        ecdsa::signature::Verifier, ecdsa::VerifyingKey, ProjectivePoint, PublicKey, Scalar,
-        Secp256k1,
+        ecdsa::signature::Verifier,
+        ecdsa::VerifyingKey,
+        sha2::{Digest, Sha256},
+        ProjectivePoint, PublicKey, Scalar, Secp256k1,
     };
     use rand_core::OsRng;
 
-    use crate::{compat::scalar_hash, math::Polynomial, protocol::run_protocol};
+    use crate::{
+        compat::scalar_hash,
+        math::Polynomial,
+        presign::PresignShareCommitment,
+        protocol::{internal::tamper_payload, run_protocol, Action, Tamper},
+    };
 
     use super::*;
 
@@ -181,6 +555,7 @@ mod test {
                     big_r: big_k,
                     k: g.evaluate(&p_scalar),
                     sigma: h.evaluate(&p_scalar),
+                    commitments: None,
                 };
                 let protocol = sign(
                     &participants,
@@ -188,17 +563,278 @@ mod test {
                     public_key,
                     presignature,
                     scalar_hash(msg),
+                    b"test sign",
+                )?;
+                protocols.push((*p, Box::new(protocol)));
+            }
+
+            let result = run_protocol(protocols)?;
+            let full_sig = result[0].1.clone();
+            let sig = Signature::from_scalars(
+                compat::x_coordinate::<Secp256k1>(&full_sig.big_r),
+                full_sig.s,
+            )?;
+            let vk = VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap());
+            vk.verify(&msg[..], &sig)?;
+            let recovered = VerifyingKey::recover_from_prehash(
+                &Sha256::digest(msg),
+                &sig,
+                full_sig.recovery_id(),
+            )?;
+            assert_eq!(recovered, vk);
+        }
+        Ok(())
+    }
+
+    /// Create a signature directly, without running the protocol.
+    fn direct_signature(x: Scalar, msg_hash: Scalar) -> FullSignature<Secp256k1> {
+        let k = Scalar::random(&mut OsRng);
+        let big_r = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let r = compat::x_coordinate::<Secp256k1>(&big_r);
+        FullSignature::normalized(big_r, k * (msg_hash + r * x))
+    }
+
+    #[test]
+    fn test_signature_conversions() -> Result<(), Box<dyn Error>> {
+        let msg = b"hello?";
+        let prehash = Sha256::digest(msg);
+        let x = Scalar::random(&mut OsRng);
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+        let vk = VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap());
+
+        // Run many times, so that some signatures need to be normalized.
+        for _ in 0..32 {
+            let full_sig = direct_signature(x, scalar_hash(msg));
+            let sig = full_sig.to_signature()?;
+            vk.verify(&msg[..], &sig)?;
+            assert!(sig.normalize_s().is_none());
+            assert_eq!(sig.r().as_ref(), &full_sig.r());
+
+            let recovered =
+                VerifyingKey::recover_from_prehash(&prehash, &sig, full_sig.recovery_id())?;
+            assert_eq!(recovered, vk);
+
+            assert_eq!(Signature::from_bytes(&full_sig.to_bytes()?)?, sig);
+            assert_eq!(Signature::from_der(full_sig.to_der()?.as_bytes())?, sig);
+
+            let eth = full_sig.to_ethereum()?.to_bytes();
+            assert!(eth[64] == 27 || eth[64] == 28);
+            let recovery_id = RecoveryId::from_byte(eth[64] - 27).unwrap();
+            let recovered = VerifyingKey::recover_from_prehash(
+                &prehash,
+                &Signature::from_slice(&eth[..64])?,
+                recovery_id,
+            )?;
+            assert_eq!(recovered, vk);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_message() {
+        let msg = b"hello?";
+        let x = Scalar::random(&mut OsRng);
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+        let sig = direct_signature(x, scalar_hash(msg));
+
+        assert!(sig.verify(&public_key, &scalar_hash(msg)));
+        assert!(sig.verify_message(&public_key, msg));
+        assert!(sig.verify_digest(&public_key, Sha256::new_with_prefix(msg)));
+        assert!(!sig.verify_message(&public_key, b"goodbye"));
+        let other_key = (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).to_affine();
+        assert!(!sig.verify_message(&other_key, msg));
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let mut items = Vec::new();
+        for i in 0..8u8 {
+            let x = Scalar::random(&mut OsRng);
+            let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+            let msg_hash = scalar_hash(&[i]);
+            items.push((public_key, msg_hash, direct_signature(x, msg_hash)));
+        }
+        assert!(FullSignature::<Secp256k1>::verify_batch(&mut OsRng, &[]));
+        assert!(FullSignature::verify_batch(&mut OsRng, &items));
+
+        // Negating the first point doesn't change r, so this still needs to be accepted.
+        let mut negated = items.clone();
+        negated[3].2.big_r = (-ProjectivePoint::from(negated[3].2.big_r)).to_affine();
+        assert!(negated[3].2.verify(&negated[3].0, &negated[3].1));
+        assert!(FullSignature::verify_batch(&mut OsRng, &negated));
+
+        let mut bad = items.clone();
+        bad[5].2.s += Scalar::ONE;
+        assert!(!FullSignature::verify_batch(&mut OsRng, &bad));
+
+        let mut bad = items;
+        bad[0].1 = scalar_hash(b"another message");
+        assert!(!FullSignature::verify_batch(&mut OsRng, &bad));
+    }
+
+    #[test]
+    fn test_signature_conversions_reject_zero() {
+        let sig = FullSignature::<Secp256k1> {
+            big_r: ProjectivePoint::GENERATOR.to_affine(),
+            s: Scalar::ZERO,
+        };
+        assert!(sig.to_signature().is_err());
+        assert!(sig.to_ethereum().is_err());
+    }
+
+    #[test]
+    fn test_sign_rerandomized() -> Result<(), Box<dyn Error>> {
+        let threshold = 2;
+        let msg = b"hello?";
+
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let x = f.evaluate_zero();
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let sigma = k * x;
+        let h = Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &sigma);
+
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let presignature = |p: Participant| {
+            let p_scalar = p.scalar::<Secp256k1>();
+            PresignOutput {
+                big_r: big_k,
+                k: g.evaluate(&p_scalar),
+                sigma: h.evaluate(&p_scalar),
+                commitments: None,
+            }
+        };
+
+        // The nonce should depend on the message, and the tweak.
+        let list = ParticipantList::new(&participants).unwrap();
+        let rerandomize = |msg: &[u8], tweak: &Scalar| {
+            rerandomize_presignature(
+                &list,
+                &public_key,
+                &presignature(participants[0]),
+                &scalar_hash(msg),
+                tweak,
+            )
+            .unwrap()
+            .big_r
+        };
+        let big_r = rerandomize(msg, &Scalar::ZERO);
+        assert_ne!(big_r, big_k);
+        assert_eq!(big_r, rerandomize(msg, &Scalar::ZERO));
+        assert_ne!(big_r, rerandomize(b"goodbye", &Scalar::ZERO));
+        assert_ne!(big_r, rerandomize(msg, &Scalar::ONE));
+
+        for tweak in [Scalar::ZERO, Scalar::random(&mut OsRng)] {
+            #[allow(clippy::type_complexity)]
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+            )> = Vec::with_capacity(participants.len());
+            for p in &participants {
+                let protocol = sign_rerandomized(
+                    &participants,
+                    *p,
+                    public_key,
+                    presignature(*p),
+                    scalar_hash(msg),
+                    tweak,
+                    b"test sign",
                 )?;
                 protocols.push((*p, Box::new(protocol)));
             }
 
             let result = run_protocol(protocols)?;
-            let sig = result[0].1.clone();
-            let sig =
-                Signature::from_scalars(compat::x_coordinate::<Secp256k1>(&sig.big_r), sig.s)?;
-            VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap())
+            let derived_key = (ProjectivePoint::GENERATOR * (x + tweak)).to_affine();
+            let sig = result[0].1.to_signature()?;
+            VerifyingKey::from(&PublicKey::from_affine(derived_key).unwrap())
                 .verify(&msg[..], &sig)?;
         }
         Ok(())
     }
+
+    #[test]
+    fn test_sign_with_blame() -> Result<(), Box<dyn Error>> {
+        let threshold = 2;
+        let msg = b"hello?";
+
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let x = f.evaluate_zero();
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let sigma = k * x;
+        let h = Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &sigma);
+
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let commitments = PresignCommitments {
+            big_k: (ProjectivePoint::GENERATOR * k).to_affine(),
+            big_sigma: (ProjectivePoint::GENERATOR * sigma).to_affine(),
+            shares: participants
+                .iter()
+                .map(|p| PresignShareCommitment {
+                    participant: *p,
+                    big_k: (ProjectivePoint::GENERATOR * g.evaluate(&p.scalar::<Secp256k1>()))
+                        .to_affine(),
+                    big_sigma: (ProjectivePoint::GENERATOR * h.evaluate(&p.scalar::<Secp256k1>()))
+                        .to_affine(),
+                })
+                .collect(),
+        };
+
+        let bad = participants[1];
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in &participants {
+            let p_scalar = p.scalar::<Secp256k1>();
+            let presignature = PresignOutput {
+                big_r: big_k,
+                k: g.evaluate(&p_scalar),
+                sigma: h.evaluate(&p_scalar),
+                commitments: Some(commitments.clone()),
+            };
+            let protocol = sign(
+                &participants,
+                *p,
+                public_key,
+                presignature,
+                scalar_hash(msg),
+                b"test sign with blame",
+            )?;
+            let is_bad = *p == bad;
+            protocols.push((
+                *p,
+                Box::new(Tamper {
+                    protocol,
+                    tamper: move |action: &mut Action<_>| {
+                        if let (true, Action::SendMany(m)) = (is_bad, action) {
+                            tamper_payload(m, |s_i: ScalarPrimitive<Secp256k1>| {
+                                (Scalar::from(s_i) + Scalar::ONE).into()
+                            });
+                        }
+                    },
+                }),
+            ));
+        }
+
+        match run_protocol(protocols) {
+            Err(ProtocolError::Misbehaved {
+                participant,
+                reason: Misbehavior::BadPublicShare,
+            }) => assert_eq!(participant, bad),
+            other => panic!("expected a bad public share, got {:?}", other.err()),
+        }
+        Ok(())
+    }
 }
@@ -1,129 +1,875 @@
-use k256::{AffinePoint, Secp256k1};
-use rand_core::OsRng;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fs,
+    path::Path,
+    pin::Pin,
+};
+
+use digest::Digest;
+use ecdsa::hazmat::{bits2field, DigestPrimitive};
+use elliptic_curve::ops::Reduce;
+use futures_sink::Sink;
+use k256::Secp256k1;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{OsRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use smol::{
+    channel::{self, Receiver, Sender},
+    future,
+    stream::StreamExt,
+    LocalExecutor,
+};
 
 use crate::{
-    compat::scalar_hash,
-    keygen, presign,
-    protocol::{run_protocol, Participant, Protocol},
-    sign,
-    triples::{self, TriplePub, TripleShare},
-    FullSignature, KeygenOutput, PresignArguments, PresignOutput,
+    compat::{scalar_hash, CSCurve},
+    derive_key, keygen, presign,
+    protocol::{
+        run_protocol, Action, AsyncProtocol, Codec, Corrupt, MessageData, Participant, Protocol,
+        ProtocolError, Snapshot, WIRE_VERSION,
+    },
+    serde::encode,
+    sign, sign_rerandomized,
+    testing::{run_keygen, run_presign, run_sign, run_triple_generation},
+    triples, FullSignature, KeygenOutput, PresignArguments, PresignOutput,
 };
 
-fn run_keygen(
-    participants: Vec<Participant>,
-    threshold: usize,
-) -> Vec<(Participant, KeygenOutput<Secp256k1>)> {
+/// Hash a message to a scalar, with the digest usually used with a given curve.
+fn hash_message<C: CSCurve + DigestPrimitive>(msg: &[u8]) -> C::Scalar {
+    let digest = C::Digest::new_with_prefix(msg);
+    let bytes = bits2field::<C>(&digest.finalize()).unwrap();
+    <C::Scalar as Reduce<C::Uint>>::reduce_bytes(&bytes)
+}
+
+/// Run every protocol, from key generation to signing, with a given curve.
+///
+/// Unlike the conformance tests, this signs an actual message, with the usual digest.
+fn run_e2e_message<C: CSCurve + DigestPrimitive>() {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let t = 3;
+
+    let mut keygen_result = run_keygen::<C>(&participants, t);
+    keygen_result.sort_by_key(|(p, _)| *p);
+
+    let public_key = keygen_result[0].1.public_key;
+    assert_eq!(keygen_result[0].1.public_key, keygen_result[1].1.public_key);
+    assert_eq!(keygen_result[1].1.public_key, keygen_result[2].1.public_key);
+
+    let (pub0, shares0) = run_triple_generation::<C>(&participants, t);
+    let (pub1, shares1) = run_triple_generation::<C>(&participants, t);
+
+    let mut presign_result = run_presign(keygen_result, shares0, shares1, &pub0, &pub1, t);
+    presign_result.sort_by_key(|(p, _)| *p);
+
+    let msg = b"hello world";
+
+    let sign_result = run_sign(presign_result, public_key, hash_message::<C>(msg));
+    for (_, sig) in &sign_result {
+        assert!(sig.verify_message(&public_key, msg));
+    }
+}
+
+/// Run the end to end tests with a given curve, along with the conformance tests.
+macro_rules! e2e_tests {
+    ($module:ident, $curve:ty) => {
+        mod $module {
+            #[test]
+            fn test_e2e_message() {
+                super::run_e2e_message::<$curve>();
+            }
+
+            crate::conformance_tests!(conformance, $curve);
+        }
+    };
+}
+
+e2e_tests!(secp256k1, k256::Secp256k1);
+#[cfg(feature = "p256")]
+e2e_tests!(nist_p256, p256::NistP256);
+#[cfg(feature = "p384")]
+e2e_tests!(nist_p384, p384::NistP384);
+
+#[test]
+fn test_e2e_derived_key() {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let t = 2;
+
+    let mut keygen_result = run_keygen::<Secp256k1>(&participants, t);
+    keygen_result.sort_by_key(|(p, _)| *p);
+    let root_key = keygen_result[0].1.public_key;
+
+    let derived = derive_key::<Secp256k1>(&root_key, &[1u8; 32], &[0, 7]).unwrap();
+    assert_ne!(derived.public_key, root_key);
+    let msg = b"hello world";
+    let check = |sig: &FullSignature<Secp256k1>| {
+        assert!(sig.verify_message(&derived.public_key, msg));
+        assert!(!sig.verify_message(&root_key, msg));
+    };
+
+    // Presignatures created with the root key can be adjusted to the derived key.
+    let (pub0, shares0) = triples::deal(&mut OsRng, &participants, t);
+    let (pub1, shares1) = triples::deal(&mut OsRng, &participants, t);
+    let presign_result = run_presign(keygen_result.clone(), shares0, shares1, &pub0, &pub1, t)
+        .into_iter()
+        .map(|(p, presignature)| (p, presignature.with_tweak(&derived.tweak)))
+        .collect();
+    let sign_result = run_sign(presign_result, derived.public_key, scalar_hash(msg));
+    check(&sign_result[0].1);
+
+    // Alternatively, the key shares themselves can be adjusted.
+    let derived_keys: Vec<_> = keygen_result
+        .iter()
+        .map(|(p, keygen_out)| (*p, keygen_out.with_tweak(&derived.tweak)))
+        .collect();
+    assert!(derived_keys
+        .iter()
+        .all(|(_, out)| out.public_key == derived.public_key));
+    let (pub0, shares0) = triples::deal(&mut OsRng, &participants, t);
+    let (pub1, shares1) = triples::deal(&mut OsRng, &participants, t);
+    let presign_result = run_presign(derived_keys, shares0, shares1, &pub0, &pub1, t);
+    let sign_result = run_sign(presign_result, derived.public_key, scalar_hash(msg));
+    check(&sign_result[0].1);
+
+    // Presignatures can also be rerandomized with the tweak and the message.
+    let (pub0, shares0) = triples::deal(&mut OsRng, &participants, t);
+    let (pub1, shares1) = triples::deal(&mut OsRng, &participants, t);
+    let presign_result = run_presign(keygen_result, shares0, shares1, &pub0, &pub1, t);
     #[allow(clippy::type_complexity)]
     let mut protocols: Vec<(
         Participant,
-        Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
     )> = Vec::with_capacity(participants.len());
+    for (p, presignature) in presign_result {
+        let protocol = sign_rerandomized(
+            &participants,
+            p,
+            root_key,
+            presignature,
+            scalar_hash(msg),
+            derived.tweak,
+            b"test sign",
+        )
+        .unwrap();
+        protocols.push((p, Box::new(protocol)));
+    }
+    let sign_result = run_protocol(protocols).unwrap();
+    check(&sign_result[0].1);
+}
 
-    for p in participants.iter() {
-        let protocol = keygen(&participants, *p, threshold);
-        assert!(protocol.is_ok());
-        let protocol = protocol.unwrap();
-        protocols.push((*p, Box::new(protocol)));
+/// Drive a single participant asynchronously, exchanging messages over channels.
+async fn drive_async<T>(
+    me: Participant,
+    mut protocol: AsyncProtocol<'_, T>,
+    inbox: Receiver<(Participant, MessageData)>,
+    outboxes: HashMap<Participant, Sender<(Participant, MessageData)>>,
+) -> Result<T, ProtocolError> {
+    enum Event<T> {
+        Action(Option<Result<Action<T>, ProtocolError>>),
+        Message(Participant, MessageData),
+    }
+
+    loop {
+        let event = future::or(async { Event::Action(protocol.next().await) }, async {
+            match inbox.recv().await {
+                Ok((from, data)) => Event::Message(from, data),
+                // Everyone else is done, so only the protocol can make progress.
+                Err(_) => future::pending().await,
+            }
+        })
+        .await;
+        match event {
+            Event::Message(from, data) => Pin::new(&mut protocol).start_send((from, data)).unwrap(),
+            Event::Action(None) => panic!("protocol stopped without returning"),
+            Event::Action(Some(action)) => match action? {
+                Action::Wait => panic!("async protocols should never wait"),
+                Action::SendMany(m) => {
+                    for outbox in outboxes.values() {
+                        // The other participant might have already finished.
+                        let _ = outbox.try_send((me, m.clone()));
+                    }
+                }
+                Action::SendPrivate(to, m) => {
+                    let _ = outboxes[&to].try_send((me, m));
+                }
+                Action::Return(out) => return Ok(out),
+            },
+        }
     }
+}
 
-    run_protocol(protocols).unwrap()
+/// Like [run_protocol], except driving each participant concurrently as an [AsyncProtocol].
+fn run_protocol_async<T>(
+    ps: Vec<(Participant, AsyncProtocol<'_, T>)>,
+) -> Result<Vec<(Participant, T)>, ProtocolError> {
+    let (senders, receivers): (HashMap<_, _>, Vec<_>) = ps
+        .iter()
+        .map(|(p, _)| {
+            let (s, r) = channel::unbounded();
+            ((*p, s), r)
+        })
+        .unzip();
+
+    let ex = LocalExecutor::new();
+    let tasks: Vec<_> = ps
+        .into_iter()
+        .zip(receivers)
+        .map(|((p, protocol), inbox)| {
+            let outboxes = senders
+                .iter()
+                .filter(|(q, _)| **q != p)
+                .map(|(q, s)| (*q, s.clone()))
+                .collect();
+            (p, ex.spawn(drive_async(p, protocol, inbox, outboxes)))
+        })
+        .collect();
+    drop(senders);
+
+    smol::block_on(ex.run(async {
+        let mut out = Vec::with_capacity(tasks.len());
+        for (p, task) in tasks {
+            out.push((p, task.await?));
+        }
+        Ok(out)
+    }))
 }
 
-fn run_presign(
-    participants: Vec<(Participant, KeygenOutput<Secp256k1>)>,
-    shares0: Vec<TripleShare<Secp256k1>>,
-    shares1: Vec<TripleShare<Secp256k1>>,
-    pub0: &TriplePub<Secp256k1>,
-    pub1: &TriplePub<Secp256k1>,
-    threshold: usize,
-) -> Vec<(Participant, PresignOutput<Secp256k1>)> {
-    assert!(participants.len() == shares0.len());
-    assert!(participants.len() == shares1.len());
+#[test]
+fn test_keygen_async() -> Result<(), ProtocolError> {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let threshold = 2;
+
+    let mut protocols = Vec::with_capacity(participants.len());
+    for p in &participants {
+        let protocol = keygen::<Secp256k1>(&participants, *p, threshold, b"test keygen async");
+        protocols.push((*p, protocol.unwrap().into_async()));
+    }
+    let result = run_protocol_async(protocols)?;
+
+    assert_eq!(result.len(), participants.len());
+    assert_eq!(result[0].1.public_key, result[1].1.public_key);
+    assert_eq!(result[1].1.public_key, result[2].1.public_key);
+
+    Ok(())
+}
+
+#[test]
+fn test_async_fallback() -> Result<(), ProtocolError> {
+    let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+    let threshold = 2;
+
+    // Wrapping the protocols means that they can only be poked, rather than driven natively.
+    let mut protocols = Vec::with_capacity(participants.len());
+    for p in &participants {
+        let protocol = keygen::<Secp256k1>(&participants, *p, threshold, b"test async fallback");
+        let protocol = Corrupt {
+            protocol: protocol.unwrap(),
+            should_corrupt: |_: &Action<_>| false,
+        };
+        protocols.push((*p, protocol.into_async()));
+    }
+    let result = run_protocol_async(protocols)?;
+
+    assert_eq!(result.len(), participants.len());
+    assert_eq!(result[0].1.public_key, result[1].1.public_key);
+
+    Ok(())
+}
+
+#[test]
+fn test_waiting_on_silent_participant() -> Result<(), Box<dyn Error>> {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let silent = participants[2];
+
+    // Only run the first two participants, who never hear from the last one.
+    let mut protocols = Vec::new();
+    for p in &participants[..2] {
+        let protocol = keygen::<Secp256k1>(&participants, *p, 2, b"test waiting on")?;
+        protocols.push((*p, protocol));
+    }
+    let mut progress = true;
+    while progress {
+        progress = false;
+        for i in 0..protocols.len() {
+            let action = protocols[i].1.poke()?;
+            let (from, other) = (protocols[i].0, protocols[1 - i].0);
+            let m = match action {
+                Action::Wait => continue,
+                Action::SendMany(m) => m,
+                Action::SendPrivate(to, m) if to == other => m,
+                Action::SendPrivate(..) => {
+                    progress = true;
+                    continue;
+                }
+                Action::Return(_) => panic!("keygen shouldn't finish without everyone"),
+            };
+            progress = true;
+            protocols[1 - i].1.message(from, m);
+        }
+    }
+
+    for (_, protocol) in &protocols {
+        let waiting = protocol.waiting_on();
+        assert!(!waiting.is_empty());
+        for w in waiting {
+            assert_eq!(w.participants, vec![silent]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Like [run_protocol], except that the first participant gets restarted from a snapshot.
+///
+/// They stop right after producing a given number of messages, before the last one
+/// gets delivered, with `fresh` creating a new instance of their protocol to restore.
+/// The snapshot restored is the last one they stored before sending a message,
+/// so they need to send every message in it again for the protocol to finish.
+#[allow(clippy::type_complexity)]
+fn run_protocol_with_restart<T>(
+    mut ps: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+    restart_after: usize,
+    fresh: impl Fn() -> Box<dyn Protocol<Output = T>>,
+) -> Result<Vec<(Participant, T)>, Box<dyn Error>> {
+    let indices: HashMap<Participant, usize> =
+        ps.iter().enumerate().map(|(i, (p, _))| (*p, i)).collect();
+
+    let mut sent_before_restart = HashSet::new();
+    let mut sent_again = HashSet::new();
+    let mut persisted = None;
+    let mut restarted = false;
+    let size = ps.len();
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        let mut progressed = false;
+        for i in 0..size {
+            loop {
+                let from = ps[i].0;
+                let (action, snapshot) = ps[i].1.poke_with_snapshot()?;
+                if let (0, Some(snapshot)) = (i, snapshot) {
+                    persisted = Some(rmp_serde::encode::to_vec(&snapshot)?);
+                }
+                let (to, m) = match action {
+                    Action::Wait => break,
+                    Action::Return(r) => {
+                        out.push((from, r));
+                        progressed = true;
+                        break;
+                    }
+                    Action::SendMany(m) => (None, m),
+                    Action::SendPrivate(to, m) => (Some(to), m),
+                };
+                progressed = true;
+                if i == 0 && restarted {
+                    if sent_before_restart.contains(&m) {
+                        sent_again.insert(m.clone());
+                    }
+                } else if i == 0 {
+                    sent_before_restart.insert(m.clone());
+                    if sent_before_restart.len() == restart_after {
+                        // Stop before this message gets delivered.
+                        let stored: &Vec<u8> = persisted.as_ref().expect("no snapshot was stored");
+                        let snapshot: Snapshot = rmp_serde::decode::from_slice(stored)?;
+                        let mut protocol = fresh();
+                        protocol.restore(snapshot)?;
+                        ps[0].1 = protocol;
+                        restarted = true;
+                        continue;
+                    }
+                }
+                match to {
+                    None => {
+                        for j in (0..size).filter(|j| *j != i) {
+                            ps[j].1.message(from, m.clone());
+                        }
+                    }
+                    Some(to) => ps[indices[&to]].1.message(from, m),
+                }
+            }
+        }
+        if !progressed {
+            return Err("the protocol stalled".into());
+        }
+    }
+    assert!(restarted);
+    assert_eq!(sent_again, sent_before_restart);
+
+    Ok(out)
+}
+
+#[test]
+fn test_triple_generation_restored_from_snapshot() -> Result<(), Box<dyn Error>> {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let threshold = 2;
+    let session_id = b"test triple generation restored from snapshot";
 
     #[allow(clippy::type_complexity)]
     let mut protocols: Vec<(
         Participant,
-        Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        Box<dyn Protocol<Output = triples::TripleGenerationOutput<Secp256k1>>>,
     )> = Vec::with_capacity(participants.len());
+    for p in &participants {
+        let protocol = triples::generate_triple(&participants, *p, threshold, session_id)?;
+        protocols.push((*p, Box::new(protocol)));
+    }
 
-    let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
+    // Restart after the first few rounds, once some private messages have been exchanged.
+    let result = run_protocol_with_restart(protocols, 4, || {
+        Box::new(
+            triples::generate_triple(&participants, participants[0], threshold, session_id)
+                .unwrap(),
+        )
+    })?;
 
-    for (((p, keygen_out), share0), share1) in participants
-        .into_iter()
-        .zip(shares0.into_iter())
-        .zip(shares1.into_iter())
-    {
-        let protocol = presign(
-            &participant_list,
-            p,
-            PresignArguments {
-                triple0: (share0, pub0.clone()),
-                triple1: (share1, pub1.clone()),
-                keygen_out,
-                threshold,
-            },
+    assert_eq!(result.len(), participants.len());
+    for (_, (_, triple_pub)) in &result[1..] {
+        assert_eq!(triple_pub, &result[0].1 .1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_restore_rejects_different_messages() -> Result<(), Box<dyn Error>> {
+    let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+    let me = participants[0];
+    let session_id = b"test restore rejects different messages";
+
+    let mut protocol = keygen::<Secp256k1>(&participants, me, 2, session_id)?;
+    let (action, snapshot) = protocol.poke_with_snapshot()?;
+    let snapshot = snapshot.unwrap();
+    // The snapshot to store already contains the message to send.
+    match action {
+        Action::SendMany(m) => assert_eq!(snapshot.sent, vec![(None, m)]),
+        _ => panic!("expected a message to everyone"),
+    }
+    assert!(protocol.poke_with_snapshot()?.1.is_none());
+
+    // A protocol can't be restored once it has started.
+    assert!(protocol.restore(snapshot.clone()).is_err());
+    // Nor can it be restored into a different session.
+    let mut other = keygen::<Secp256k1>(&participants, me, 2, b"another session")?;
+    assert!(other.restore(snapshot.clone()).is_err());
+
+    // If the protocol were to send a different message than before, it needs to fail.
+    let mut tampered = snapshot.clone();
+    *tampered.sent[0].1.last_mut().unwrap() ^= 1;
+    let mut restored = keygen::<Secp256k1>(&participants, me, 2, session_id)?;
+    restored.restore(tampered)?;
+    assert!(restored.poke().is_err());
+
+    // Otherwise, the same message is sent again, in case it was never delivered.
+    let sent = snapshot.sent[0].1.clone();
+    let mut restored = keygen::<Secp256k1>(&participants, me, 2, session_id)?;
+    restored.restore(snapshot)?;
+    assert!(matches!(restored.poke()?, Action::SendMany(m) if m == sent));
+    assert!(matches!(restored.poke()?, Action::Wait));
+
+    Ok(())
+}
+
+/// A message sent during a recorded run of a protocol.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct RecordedMessage {
+    from: Participant,
+    /// The recipient of a private message, or `None` if the message was sent to everyone.
+    to: Option<Participant>,
+    #[serde(with = "hex")]
+    data: MessageData,
+}
+
+/// A known answer test: everything which happened during a run of a protocol.
+///
+/// Each participant draws their randomness from a ChaCha20 rng with a given seed,
+/// which makes the messages and outputs of the run deterministic.
+/// The messages are sorted by sender, recipient, and then contents.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct TestVector {
+    wire_version: u16,
+    #[serde(with = "hex")]
+    session_id: Vec<u8>,
+    participants: Vec<Participant>,
+    threshold: usize,
+    seeds: Vec<String>,
+    messages: Vec<RecordedMessage>,
+    /// The output of each participant, in the same order as the participants, encoded with rmp-serde.
+    outputs: Vec<String>,
+}
+
+/// Like [run_protocol], except recording every message sent, in order.
+#[allow(clippy::type_complexity)]
+fn run_protocol_recorded<T>(
+    mut ps: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+) -> Result<(Vec<RecordedMessage>, Vec<(Participant, T)>), ProtocolError> {
+    let indices: HashMap<Participant, usize> =
+        ps.iter().enumerate().map(|(i, (p, _))| (*p, i)).collect();
+
+    let mut messages = Vec::new();
+    let size = ps.len();
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        for i in 0..size {
+            loop {
+                let from = ps[i].0;
+                match ps[i].1.poke()? {
+                    Action::Wait => break,
+                    Action::Return(r) => {
+                        out.push((from, r));
+                        break;
+                    }
+                    Action::SendMany(data) => {
+                        for j in (0..size).filter(|j| *j != i) {
+                            ps[j].1.message(from, data.clone());
+                        }
+                        messages.push(RecordedMessage {
+                            from,
+                            to: None,
+                            data,
+                        });
+                    }
+                    Action::SendPrivate(to, data) => {
+                        ps[indices[&to]].1.message(from, data.clone());
+                        messages.push(RecordedMessage {
+                            from,
+                            to: Some(to),
+                            data,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((messages, out))
+}
+
+/// Run a protocol with seeded randomness, checking the run against a known answer test.
+///
+/// The test vectors live in the `test-vectors` directory, and a missing vector is a failure.
+/// Setting `UPDATE_TEST_VECTORS=1` writes every vector from the current run instead;
+/// this should only ever be done after an intentional change to the wire format,
+/// and fails if any message changed without bumping [WIRE_VERSION].
+///
+/// This returns the outputs of each participant, in the same order as the participants.
+#[allow(clippy::type_complexity)]
+fn check_test_vector<T: Serialize>(
+    name: &str,
+    participants: &[Participant],
+    threshold: usize,
+    make: impl Fn(Participant, &[u8]) -> Box<dyn Protocol<Output = T>>,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let session_id = format!("cait-sith test vector {}", name).into_bytes();
+    let seeds: Vec<[u8; 32]> = participants
+        .iter()
+        .map(|p| {
+            let mut seed = [0u8; 32];
+            seed[..4].copy_from_slice(&p.bytes());
+            seed[4..4 + name.len()].copy_from_slice(name.as_bytes());
+            seed
+        })
+        .collect();
+
+    let mut protocols = Vec::with_capacity(participants.len());
+    for (p, seed) in participants.iter().zip(&seeds) {
+        let mut protocol = make(*p, &session_id);
+        protocol.seed_from_rng(&mut ChaCha20Rng::from_seed(*seed))?;
+        protocols.push((*p, protocol));
+    }
+    let (mut messages, mut outputs) = run_protocol_recorded(protocols)?;
+    // Concurrent parts of a protocol can interleave their messages differently between runs.
+    messages.sort_by(|a, b| (a.from, a.to, &a.data).cmp(&(b.from, b.to, &b.data)));
+    outputs.sort_by_key(|(p, _)| participants.iter().position(|q| q == p));
+
+    let actual = TestVector {
+        wire_version: WIRE_VERSION,
+        session_id,
+        participants: participants.to_vec(),
+        threshold,
+        seeds: seeds.iter().map(hex::encode).collect(),
+        messages,
+        outputs: outputs
+            .iter()
+            .map(|(_, out)| hex::encode(encode(out)))
+            .collect(),
+    };
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("test-vectors")
+        .join(format!("{}.json", name));
+    if env::var_os("UPDATE_TEST_VECTORS").is_some() {
+        // Changing the messages of a protocol requires a new wire version.
+        if let Ok(existing) = fs::read_to_string(&path) {
+            let existing: TestVector = serde_json::from_str(&existing)?;
+            assert!(
+                existing.wire_version != WIRE_VERSION || existing.messages == actual.messages,
+                "the messages of {} changed, so WIRE_VERSION needs to be bumped before updating its vector",
+                name
+            );
+        }
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(&actual)? + "\n")?;
+    } else {
+        let expected = fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "failed to read the {} vector at {}, which can be written with UPDATE_TEST_VECTORS=1: {}",
+                name,
+                path.display(),
+                e
+            )
+        })?;
+        let expected: TestVector = serde_json::from_str(&expected)?;
+        assert_eq!(
+            expected.wire_version, WIRE_VERSION,
+            "the {} vector is for another wire version, and needs to be regenerated",
+            name
         );
-        assert!(protocol.is_ok());
-        let protocol = protocol.unwrap();
-        protocols.push((p, Box::new(protocol)));
+        assert_eq!(expected.session_id, actual.session_id);
+        assert_eq!(expected.seeds, actual.seeds);
+        assert_eq!(expected.messages.len(), actual.messages.len());
+        for (i, (e, a)) in expected.messages.iter().zip(&actual.messages).enumerate() {
+            assert_eq!(
+                e, a,
+                "message {} of {} differs, so the wire format changed without bumping WIRE_VERSION",
+                i, name
+            );
+        }
+        assert_eq!(expected, actual);
     }
 
-    run_protocol(protocols).unwrap()
+    Ok(outputs.into_iter().map(|(_, out)| out).collect())
+}
+
+#[test]
+fn test_seeded_protocols_are_deterministic() -> Result<(), Box<dyn Error>> {
+    let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+    let run = || -> Result<_, Box<dyn Error>> {
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in &participants {
+            let mut protocol = keygen(&participants, *p, 2, b"test seeded protocols")?;
+            protocol.seed_from_rng(&mut ChaCha20Rng::from_seed([u32::from(*p) as u8; 32]))?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let (messages, outputs) = run_protocol_recorded(protocols)?;
+        Ok((messages, encode(&outputs)))
+    };
+    assert_eq!(run()?, run()?);
+
+    // A protocol can't be seeded once it has started.
+    let mut protocol = keygen::<Secp256k1>(&participants, participants[0], 2, b"")?;
+    protocol.poke()?;
+    assert!(protocol.seed_from_rng(&mut OsRng).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_vectors() -> Result<(), Box<dyn Error>> {
+    // Two participants keep the vectors for triple generation reasonably small.
+    let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+    let threshold = 2;
+
+    let keygen_out = check_test_vector("keygen", &participants, threshold, |p, session_id| {
+        Box::new(keygen::<Secp256k1>(&participants, p, threshold, session_id).unwrap())
+    })?;
+
+    let triples_out = check_test_vector("triples", &participants, threshold, |p, session_id| {
+        Box::new(
+            triples::generate_triple_many::<Secp256k1>(&participants, p, threshold, 2, session_id)
+                .unwrap(),
+        )
+    })?;
+
+    // Presigning and signing use the outputs of the previous protocols as their inputs.
+    let presign_out = check_test_vector("presign", &participants, threshold, |p, session_id| {
+        let i = participants.iter().position(|q| *q == p).unwrap();
+        let args = PresignArguments {
+            triple0: triples_out[i][0].clone(),
+            triple1: triples_out[i][1].clone(),
+            keygen_out: keygen_out[i].clone(),
+            threshold,
+        };
+        Box::new(presign(&participants, p, args, session_id).unwrap())
+    })?;
+
+    let public_key = keygen_out[0].public_key;
+    let msg_hash = hash_message::<Secp256k1>(b"cait-sith test vector");
+    let signatures = check_test_vector("sign", &participants, threshold, |p, session_id| {
+        let i = participants.iter().position(|q| *q == p).unwrap();
+        Box::new(
+            sign(
+                &participants,
+                p,
+                public_key,
+                presign_out[i].clone(),
+                msg_hash,
+                session_id,
+            )
+            .unwrap(),
+        )
+    })?;
+    assert!(signatures[0].verify(&public_key, &msg_hash));
+
+    Ok(())
 }
 
+/// Like [run_protocol], except with every participant using a given codec.
 #[allow(clippy::type_complexity)]
-fn run_sign(
-    participants: Vec<(Participant, PresignOutput<Secp256k1>)>,
-    public_key: AffinePoint,
-    msg: &[u8],
-) -> Vec<(Participant, FullSignature<Secp256k1>)> {
+fn run_protocol_with_codec<T>(
+    mut ps: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+    codec: Codec,
+) -> Result<Vec<(Participant, T)>, Box<dyn Error>> {
+    for (_, protocol) in &mut ps {
+        protocol.set_codec(codec)?;
+    }
+    let mut out = run_protocol(ps)?;
+    out.sort_by_key(|(p, _)| *p);
+    Ok(out)
+}
+
+#[test]
+fn test_canonical_codec() -> Result<(), Box<dyn Error>> {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let threshold = 2;
+    let codec = Codec::Canonical;
+
+    #[allow(clippy::type_complexity)]
     let mut protocols: Vec<(
         Participant,
-        Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+        Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
+    for p in &participants {
+        let protocol = keygen(&participants, *p, threshold, b"test canonical keygen")?;
+        protocols.push((*p, Box::new(protocol)));
+    }
+    let keygen_out = run_protocol_with_codec(protocols, codec)?;
+    let public_key = keygen_out[0].1.public_key;
+
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = Vec<triples::TripleGenerationOutput<Secp256k1>>>>,
     )> = Vec::with_capacity(participants.len());
+    for p in &participants {
+        let protocol = triples::generate_triple_many(
+            &participants,
+            *p,
+            threshold,
+            2,
+            b"test canonical triples",
+        )?;
+        protocols.push((*p, Box::new(protocol)));
+    }
+    let triples_out = run_protocol_with_codec(protocols, codec)?;
 
-    let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
+    for ((p, keygen_out), (_, triples)) in keygen_out.into_iter().zip(&triples_out) {
+        let args = PresignArguments {
+            triple0: triples[0].clone(),
+            triple1: triples[1].clone(),
+            keygen_out,
+            threshold,
+        };
+        let protocol = presign(&participants, p, args, b"test canonical presign")?;
+        protocols.push((p, Box::new(protocol)));
+    }
+    let presign_out = run_protocol_with_codec(protocols, codec)?;
 
-    for (p, presign_out) in participants.into_iter() {
+    let msg_hash = hash_message::<Secp256k1>(b"hello world");
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+    )> = Vec::with_capacity(participants.len());
+    for (p, presign_out) in presign_out {
         let protocol = sign(
-            &participant_list,
+            &participants,
             p,
             public_key,
             presign_out,
-            scalar_hash(msg),
-        );
-        assert!(protocol.is_ok());
-        let protocol = protocol.unwrap();
+            msg_hash,
+            b"test canonical sign",
+        )?;
         protocols.push((p, Box::new(protocol)));
     }
+    for (_, sig) in run_protocol_with_codec(protocols, codec)? {
+        assert!(sig.verify(&public_key, &msg_hash));
+    }
 
-    run_protocol(protocols).unwrap()
+    Ok(())
 }
 
 #[test]
-fn test_e2e() {
-    let participants = vec![
-        Participant::from(0u32),
-        Participant::from(1u32),
-        Participant::from(2u32),
-    ];
-    let t = 3;
+fn test_codec_mismatch() -> Result<(), Box<dyn Error>> {
+    let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+    let session_id = b"test codec mismatch";
 
-    let mut keygen_result = run_keygen(participants.clone(), t);
-    keygen_result.sort_by_key(|(p, _)| *p);
-
-    let public_key = keygen_result[0].1.public_key;
-    assert_eq!(keygen_result[0].1.public_key, keygen_result[1].1.public_key);
-    assert_eq!(keygen_result[1].1.public_key, keygen_result[2].1.public_key);
-
-    let (pub0, shares0) = triples::deal(&mut OsRng, &participants, t);
-    let (pub1, shares1) = triples::deal(&mut OsRng, &participants, t);
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = _>>)> = Vec::new();
+    for p in &participants {
+        let mut protocol = keygen::<Secp256k1>(&participants, *p, 2, session_id)?;
+        if *p == participants[1] {
+            protocol.set_codec(Codec::Canonical)?;
+        }
+        protocols.push((*p, Box::new(protocol)));
+    }
+    // Either side can notice the mismatch first, but they always blame each other.
+    match run_protocol(protocols) {
+        Err(ProtocolError::CodecMismatch {
+            participant,
+            ours: Codec::MessagePack,
+            theirs: 2,
+        }) => assert_eq!(participant, participants[1]),
+        Err(ProtocolError::CodecMismatch {
+            participant,
+            ours: Codec::Canonical,
+            theirs: 1,
+        }) => assert_eq!(participant, participants[0]),
+        other => panic!("expected a codec mismatch, got {:?}", other.err()),
+    }
 
-    let mut presign_result = run_presign(keygen_result, shares0, shares1, &pub0, &pub1, t);
-    presign_result.sort_by_key(|(p, _)| *p);
+    // The codec can't change once the protocol has started.
+    let mut protocol = keygen::<Secp256k1>(&participants, participants[0], 2, session_id)?;
+    protocol.set_codec(Codec::Canonical)?;
+    assert!(matches!(protocol.poke()?, Action::SendMany(_)));
+    assert!(protocol.set_codec(Codec::MessagePack).is_err());
 
-    let msg = b"hello world";
+    // Restoring a snapshot also restores its codec, so the same message is sent again.
+    let snapshot = protocol.snapshot().unwrap();
+    let sent = snapshot.sent[0].1.clone();
+    let mut restored = keygen::<Secp256k1>(&participants, participants[0], 2, session_id)?;
+    restored.restore(snapshot)?;
+    assert!(matches!(restored.poke()?, Action::SendMany(m) if m == sent));
 
-    run_sign(presign_result, public_key, msg);
+    Ok(())
 }
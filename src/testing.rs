@@ -0,0 +1,417 @@
+//! Utilities for testing custom implementations of [CSCurve].
+//!
+//! This module is only available with the `testing` feature.
+//!
+//! Implementing [CSCurve] for a new curve requires a few properties which the
+//! type system can't check, like points surviving serialization, or scalars
+//! being sampled without bias. The `check_*` functions here test these properties,
+//! along with running the protocols end to end, panicking if anything goes wrong.
+//! The easiest way to run all of them is with the [crate::conformance_tests] macro:
+//!
+//! ```ignore
+//! cait_sith::conformance_tests!(my_curve, MyCurve);
+//! ```
+//!
+//! This creates a module named `my_curve`, with one test for each check.
+//! The functions used to run each protocol are also exposed, for writing other tests.
+use std::collections::HashSet;
+
+use elliptic_curve::{Field, Group, PrimeField, ScalarPrimitive};
+use rand_core::{CryptoRng, OsRng, RngCore};
+
+use crate::{
+    compat::{CSCurve, SerializablePoint},
+    constants::SECURITY_PARAMETER,
+    keygen, presign,
+    protocol::{run_protocol, Participant, Protocol},
+    reshare,
+    serde::{decode, encode},
+    sign, split_key,
+    triples::{self, TripleGenerationOutput, TriplePub, TripleShare},
+    FullSignature, KeygenOutput, PresignArguments, PresignOutput,
+};
+
+/// The number of scalars to sample when checking their distribution.
+const SAMPLE_COUNT: usize = 1000;
+
+/// Run the key generation protocol, with every participant.
+pub fn run_keygen<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+) -> Vec<(Participant, KeygenOutput<C>)> {
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = KeygenOutput<C>>>)> =
+        Vec::with_capacity(participants.len());
+
+    for p in participants {
+        let protocol = keygen(participants, *p, threshold, b"test keygen").unwrap();
+        protocols.push((*p, Box::new(protocol)));
+    }
+
+    run_protocol(protocols).unwrap()
+}
+
+/// Run the resharing protocol, from the result of key generation to a new set of participants.
+pub fn run_reshare<C: CSCurve>(
+    old: &[(Participant, KeygenOutput<C>)],
+    old_threshold: usize,
+    new_participants: &[Participant],
+    new_threshold: usize,
+) -> Vec<(Participant, KeygenOutput<C>)> {
+    let old_participants: Vec<Participant> = old.iter().map(|(p, _)| *p).collect();
+    let public_key = old[0].1.public_key;
+
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = KeygenOutput<C>>>)> =
+        Vec::with_capacity(new_participants.len());
+
+    for p in new_participants {
+        let my_share = old
+            .iter()
+            .find(|(q, _)| q == p)
+            .map(|(_, out)| out.private_share);
+        let protocol = reshare(
+            &old_participants,
+            old_threshold,
+            new_participants,
+            new_threshold,
+            *p,
+            my_share,
+            public_key,
+            b"test reshare",
+        )
+        .unwrap();
+        protocols.push((*p, Box::new(protocol)));
+    }
+
+    run_protocol(protocols).unwrap()
+}
+
+/// Generate a triple, by running the full protocol, rather than dealing it.
+///
+/// The shares are returned in the same order as the participants.
+pub fn run_triple_generation<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+) -> (TriplePub<C>, Vec<TripleShare<C>>) {
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(
+        Participant,
+        Box<dyn Protocol<Output = TripleGenerationOutput<C>>>,
+    )> = Vec::with_capacity(participants.len());
+
+    for p in participants {
+        let protocol =
+            triples::generate_triple(participants, *p, threshold, b"test triple generation")
+                .unwrap();
+        protocols.push((*p, Box::new(protocol)));
+    }
+
+    let mut result = run_protocol(protocols).unwrap();
+    result.sort_by_key(|(p, _)| participants.iter().position(|q| q == p));
+    let triple_pub = result[0].1 .1.clone();
+    assert!(result.iter().all(|(_, (_, other))| *other == triple_pub));
+    (
+        triple_pub,
+        result.into_iter().map(|(_, (share, _))| share).collect(),
+    )
+}
+
+/// Run the presigning protocol, with every participant.
+///
+/// The shares of the triples need to be in the same order as the participants.
+pub fn run_presign<C: CSCurve>(
+    participants: Vec<(Participant, KeygenOutput<C>)>,
+    shares0: Vec<TripleShare<C>>,
+    shares1: Vec<TripleShare<C>>,
+    pub0: &TriplePub<C>,
+    pub1: &TriplePub<C>,
+    threshold: usize,
+) -> Vec<(Participant, PresignOutput<C>)> {
+    assert!(participants.len() == shares0.len());
+    assert!(participants.len() == shares1.len());
+
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = PresignOutput<C>>>)> =
+        Vec::with_capacity(participants.len());
+
+    let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
+
+    for (((p, keygen_out), share0), share1) in participants.into_iter().zip(shares0).zip(shares1) {
+        let protocol = presign(
+            &participant_list,
+            p,
+            PresignArguments {
+                triple0: (share0, pub0.clone()),
+                triple1: (share1, pub1.clone()),
+                keygen_out,
+                threshold,
+            },
+            b"test presign",
+        )
+        .unwrap();
+        protocols.push((p, Box::new(protocol)));
+    }
+
+    run_protocol(protocols).unwrap()
+}
+
+/// Run the signing protocol, with every participant.
+pub fn run_sign<C: CSCurve>(
+    participants: Vec<(Participant, PresignOutput<C>)>,
+    public_key: C::AffinePoint,
+    msg_hash: C::Scalar,
+) -> Vec<(Participant, FullSignature<C>)> {
+    #[allow(clippy::type_complexity)]
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = FullSignature<C>>>)> =
+        Vec::with_capacity(participants.len());
+
+    let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
+
+    for (p, presign_out) in participants {
+        let protocol = sign(
+            &participant_list,
+            p,
+            public_key,
+            presign_out,
+            msg_hash,
+            b"test sign",
+        )
+        .unwrap();
+        protocols.push((p, Box::new(protocol)));
+    }
+
+    run_protocol(protocols).unwrap()
+}
+
+/// Check that points and scalars survive being serialized, and deserialized.
+pub fn check_serde_round_trip<C: CSCurve>() {
+    let scalars = [
+        C::Scalar::ZERO,
+        C::Scalar::ONE,
+        -C::Scalar::ONE,
+        C::Scalar::random(&mut OsRng),
+    ];
+    for x in scalars {
+        assert_eq!(
+            Option::<C::Scalar>::from(C::Scalar::from_repr(x.to_repr())),
+            Some(x),
+            "scalar representation did not round trip"
+        );
+        let primitive: ScalarPrimitive<C> = x.into();
+        let decoded: ScalarPrimitive<C> = decode(&encode(&primitive)).unwrap();
+        assert_eq!(
+            C::Scalar::from(decoded),
+            x,
+            "scalar serialization did not round trip"
+        );
+
+        let point = C::ProjectivePoint::generator() * x;
+        let decoded: SerializablePoint<C> =
+            decode(&encode(&SerializablePoint::<C>::from_projective(&point))).unwrap();
+        assert_eq!(
+            decoded.to_projective(),
+            point,
+            "point serialization did not round trip"
+        );
+    }
+
+    let participants = [Participant::from(0u32), Participant::from(1u32)];
+    for out in split_key::<C>(&mut OsRng, &C::Scalar::random(&mut OsRng), &participants, 2).unwrap()
+    {
+        let decoded: KeygenOutput<C> = decode(&encode(&out)).unwrap();
+        assert_eq!(decoded.private_share, out.private_share);
+        assert_eq!(decoded.public_key, out.public_key);
+        assert_eq!(decoded.commitment, out.commitment);
+    }
+}
+
+/// Check that [CSCurve::BITS] is large enough to hold any scalar.
+///
+/// The multiplication of triples relies on this for its security.
+pub fn check_bits<C: CSCurve>() {
+    assert!(
+        C::BITS >= usize::try_from(C::Scalar::NUM_BITS).unwrap(),
+        "BITS is smaller than the number of bits in a scalar"
+    );
+}
+
+/// A wrapper around an rng, counting how many bytes were pulled from it.
+struct CountingRng {
+    count: usize,
+}
+
+impl RngCore for CountingRng {
+    fn next_u32(&mut self) -> u32 {
+        self.count += 4;
+        OsRng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.count += 8;
+        OsRng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.count += dest.len();
+        OsRng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.count += dest.len();
+        OsRng.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for CountingRng {}
+
+/// Check that [CSCurve::sample_scalar_constant_time] behaves as it should.
+///
+/// Sampling needs to pull the same amount of randomness every time, and this needs
+/// to be enough to leave a negligible bias, i.e. at least the size of a scalar,
+/// with an extra 128 bits to spare, for our security parameter.
+/// We also sanity check the distribution of the scalars this produces.
+pub fn check_sampling<C: CSCurve>() {
+    let repr_len = C::Scalar::ZERO.to_repr().as_ref().len();
+    let mut rng = CountingRng { count: 0 };
+    let mut used = None;
+    let mut seen = HashSet::with_capacity(SAMPLE_COUNT);
+    let mut byte_values: Vec<HashSet<u8>> = vec![HashSet::new(); repr_len];
+    let mut odd = 0;
+    for _ in 0..SAMPLE_COUNT {
+        rng.count = 0;
+        let x = C::sample_scalar_constant_time(&mut rng);
+        assert_eq!(
+            *used.get_or_insert(rng.count),
+            rng.count,
+            "sampling did not always use the same amount of randomness"
+        );
+        let repr = x.to_repr();
+        for (values, byte) in byte_values.iter_mut().zip(repr.as_ref()) {
+            values.insert(*byte);
+        }
+        if bool::from(x.is_odd()) {
+            odd += 1;
+        }
+        seen.insert(repr.as_ref().to_vec());
+    }
+
+    let used = used.unwrap();
+    let needed = (usize::try_from(C::Scalar::NUM_BITS).unwrap() + SECURITY_PARAMETER).div_ceil(8);
+    assert!(
+        used >= needed,
+        "sampling used {} bytes of randomness, but needs at least {}",
+        used,
+        needed
+    );
+    assert_eq!(seen.len(), SAMPLE_COUNT, "sampling repeated a scalar");
+    // Failing these happens with negligible probability, for a uniform distribution.
+    assert!(
+        byte_values.iter().all(|values| values.len() > 1),
+        "some byte of the sampled scalars never changed"
+    );
+    assert!(
+        (SAMPLE_COUNT * 2 / 5..SAMPLE_COUNT * 3 / 5).contains(&odd),
+        "sampled scalars were odd {} times out of {}",
+        odd,
+        SAMPLE_COUNT
+    );
+}
+
+/// Check that the protocols work end to end, from key generation to signing.
+///
+/// This generates the triples with the full protocol, which can take a while.
+pub fn check_e2e<C: CSCurve>() {
+    let participants = [
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let threshold = 3;
+
+    let mut keygen_result = run_keygen::<C>(&participants, threshold);
+    keygen_result.sort_by_key(|(p, _)| *p);
+    let public_key = keygen_result[0].1.public_key;
+    assert!(keygen_result
+        .iter()
+        .all(|(_, out)| out.public_key == public_key));
+
+    let (pub0, shares0) = run_triple_generation::<C>(&participants, threshold);
+    let (pub1, shares1) = run_triple_generation::<C>(&participants, threshold);
+    let presign_result = run_presign(keygen_result, shares0, shares1, &pub0, &pub1, threshold);
+
+    let msg_hash = C::Scalar::random(&mut OsRng);
+    let sign_result = run_sign(presign_result, public_key, msg_hash);
+    for (_, sig) in &sign_result {
+        assert!(sig.verify(&public_key, &msg_hash));
+    }
+}
+
+/// Check that a key can be reshared, and then used to sign.
+pub fn check_reshare<C: CSCurve>() {
+    let participants = [
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+        Participant::from(3u32),
+    ];
+
+    let keygen_result = run_keygen::<C>(&participants[..3], 2);
+    let public_key = keygen_result[0].1.public_key;
+
+    // Drop one of the old participants, add a new one, and raise the threshold.
+    let new_participants = &participants[1..];
+    let mut reshare_result = run_reshare(&keygen_result, 2, new_participants, 3);
+    reshare_result.sort_by_key(|(p, _)| new_participants.iter().position(|q| q == p));
+    for (p, out) in &reshare_result {
+        assert_eq!(out.public_key, public_key);
+        assert_eq!(out.commitment, reshare_result[0].1.commitment);
+        assert!(out.commitment.verify_share(*p, &out.private_share));
+    }
+
+    let (pub0, shares0) = triples::deal(&mut OsRng, new_participants, 3);
+    let (pub1, shares1) = triples::deal(&mut OsRng, new_participants, 3);
+    let presign_result = run_presign(reshare_result, shares0, shares1, &pub0, &pub1, 3);
+
+    let msg_hash = C::Scalar::random(&mut OsRng);
+    let sign_result = run_sign(presign_result, public_key, msg_hash);
+    for (_, sig) in &sign_result {
+        assert!(sig.verify(&public_key, &msg_hash));
+    }
+}
+
+/// Generate a module of tests, checking that a curve works with this library.
+///
+/// This takes the name of the module to create, and the curve to test,
+/// with one test for each of the checks in [crate::testing].
+#[macro_export]
+macro_rules! conformance_tests {
+    ($module:ident, $curve:ty) => {
+        mod $module {
+            #[test]
+            fn test_serde_round_trip() {
+                $crate::testing::check_serde_round_trip::<$curve>();
+            }
+
+            #[test]
+            fn test_bits() {
+                $crate::testing::check_bits::<$curve>();
+            }
+
+            #[test]
+            fn test_sampling() {
+                $crate::testing::check_sampling::<$curve>();
+            }
+
+            #[test]
+            fn test_e2e() {
+                $crate::testing::check_e2e::<$curve>();
+            }
+
+            #[test]
+            fn test_reshare() {
+                $crate::testing::check_reshare::<$curve>();
+            }
+        }
+    };
+}
@@ -1,6 +1,5 @@
 use ck_meow::Meow;
 use elliptic_curve::{Field, Group};
-use rand_core::OsRng;
 use smol::stream::{self, StreamExt};
 use subtle::ConditionallySelectable;
 
@@ -9,7 +8,7 @@ use crate::{
     constants::SECURITY_PARAMETER,
     protocol::{
         internal::{make_protocol, Context, PrivateChannel},
-        run_two_party_protocol, Participant, ProtocolError,
+        run_two_party_protocol, Participant, ProtocolError, ProtocolId,
     },
     serde::encode,
 };
@@ -43,7 +42,7 @@ pub async fn batch_random_ot_sender<C: CSCurve>(
     mut chan: PrivateChannel,
 ) -> Result<BatchRandomOTOutputSender, ProtocolError> {
     // Spec 1
-    let y = C::Scalar::random(&mut OsRng);
+    let y = C::Scalar::random(&mut chan.rng(b"batch random ot y"));
     let big_y = C::ProjectivePoint::generator() * y;
     let big_z = big_y * y;
 
@@ -88,13 +87,13 @@ pub async fn batch_random_ot_receiver<C: CSCurve>(
         ));
     }
 
-    let delta = BitVector::random(&mut OsRng);
+    let delta = BitVector::random(&mut chan.rng(b"batch random ot delta"));
 
     let tasks = delta.bits().enumerate().map(|(i, d_i)| {
         let mut chan = chan.child(i as u64);
         ctx.spawn(async move {
             // Step 4
-            let x_i = C::Scalar::random(&mut OsRng);
+            let x_i = C::Scalar::random(&mut chan.rng(b"batch random ot x_i"));
             let mut big_x_i = C::ProjectivePoint::generator() * x_i;
             big_x_i.conditional_assign(&(big_x_i + big_y), d_i);
 
@@ -119,8 +118,8 @@ pub(crate) fn run_batch_random_ot<C: CSCurve>(
 ) -> Result<(BatchRandomOTOutputSender, BatchRandomOTOutputReceiver), ProtocolError> {
     let s = Participant::from(0u32);
     let r = Participant::from(1u32);
-    let ctx_s = Context::new();
-    let ctx_r = Context::new();
+    let ctx_s = Context::new(ProtocolId::TripleGeneration, &[]);
+    let ctx_r = Context::new(ProtocolId::TripleGeneration, &[]);
 
     run_two_party_protocol(
         s,
@@ -3,17 +3,18 @@ use ck_meow::Meow;
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::constants::SECURITY_PARAMETER;
 
-pub const SEC_PARAM_64: usize = (SECURITY_PARAMETER + 64 - 1) / 64;
-pub const SEC_PARAM_8: usize = (SECURITY_PARAMETER + 8 - 1) / 8;
+pub const SEC_PARAM_64: usize = SECURITY_PARAMETER.div_ceil(64);
+pub const SEC_PARAM_8: usize = SECURITY_PARAMETER.div_ceil(8);
 
 /// Represents a vector of bits.
 ///
 /// This vector will have the size of our security parameter, which is useful
 /// for most of our OT extension protocols.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Eq, Zeroize)]
 pub struct BitVector([u64; SEC_PARAM_64]);
 
 impl BitVector {
@@ -205,7 +206,9 @@ const PRG_CTX: &[u8] = b"cait-sith v0.8.0 correlated OT PRG";
 /// rows.
 ///
 /// This is a fundamental object used for our OT extension protocol.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Since these matrices hold OT keys, they're wiped from memory when dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub struct BitMatrix(Vec<BitVector>);
 
@@ -214,7 +217,7 @@ impl BitMatrix {
     ///
     /// Each chunk will have a security parameter's worth of rows.
     pub fn random(rng: &mut impl CryptoRngCore, height: usize) -> Self {
-        assert!(height % SECURITY_PARAMETER == 0);
+        assert!(height.is_multiple_of(SECURITY_PARAMETER));
         Self((0..height).map(|_| BitVector::random(rng)).collect())
     }
 
@@ -301,7 +304,7 @@ impl SquareBitMatrix {
     /// Expand transpose expands each row to contain `chunks * SECURITY_PARAMETER` bits, and then transposes
     /// the resulting matrix.
     pub fn expand_transpose(&self, sid: &[u8], rows: usize) -> BitMatrix {
-        assert!(rows % SECURITY_PARAMETER == 0);
+        assert!(rows.is_multiple_of(SECURITY_PARAMETER));
 
         let mut meow = Meow::new(PRG_CTX);
         meow.meta_ad(b"sid", false);
@@ -310,7 +313,7 @@ impl SquareBitMatrix {
         let mut out = BitMatrix(vec![BitVector::zero(); rows]);
 
         // How many bytes to get rows bits?
-        let row8 = (rows + 7) / 8;
+        let row8 = rows.div_ceil(8);
         for (j, row) in self.matrix.0.iter().enumerate() {
             // Expand the row
             let mut expanded = vec![0u8; row8];
@@ -342,7 +345,7 @@ pub struct ChoiceVector(Vec<BitVector>);
 impl ChoiceVector {
     /// Generate a random vector with a certain number of bits.
     pub fn random(rng: &mut impl CryptoRngCore, size: usize) -> Self {
-        assert!(size > 0 && size % SECURITY_PARAMETER == 0);
+        assert!(size > 0 && size.is_multiple_of(SECURITY_PARAMETER));
 
         let data = (0..(size / SECURITY_PARAMETER))
             .map(|_| BitVector::random(rng))
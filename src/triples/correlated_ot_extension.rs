@@ -1,6 +1,6 @@
 use crate::protocol::{
     internal::{make_protocol, Context, PrivateChannel},
-    run_two_party_protocol, Participant, ProtocolError,
+    run_two_party_protocol, Participant, ProtocolError, ProtocolId,
 };
 
 use super::bits::{BitMatrix, BitVector, SquareBitMatrix};
@@ -70,8 +70,8 @@ fn run_correlated_ot(
 ) -> Result<(BitMatrix, BitMatrix), ProtocolError> {
     let s = Participant::from(0u32);
     let r = Participant::from(1u32);
-    let ctx_s = Context::new();
-    let ctx_r = Context::new();
+    let ctx_s = Context::new(ProtocolId::TripleGeneration, &[]);
+    let ctx_r = Context::new(ProtocolId::TripleGeneration, &[]);
 
     let params = CorrelatedOtParams { sid, batch_size };
 
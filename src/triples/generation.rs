@@ -1,38 +1,61 @@
 use elliptic_curve::{Field, Group, ScalarPrimitive};
 use magikitten::Transcript;
-use rand_core::OsRng;
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::{
     compat::{CSCurve, SerializablePoint},
-    crypto::{commit, hash, Digest},
+    crypto::{commit, hash, Digest, Randomizer},
     math::{GroupPolynomial, Polynomial},
     participants::{ParticipantCounter, ParticipantList, ParticipantMap},
     proofs::{dlog, dlogeq},
     protocol::{
         internal::{make_protocol, Context},
-        InitializationError, Participant, Protocol, ProtocolError,
+        InitializationError, Misbehavior, Participant, Protocol, ProtocolError, ProtocolId,
     },
     serde::encode,
 };
 
-use super::{multiplication::multiplication, TriplePub, TripleShare};
+use super::{multiplication::multiplication_many, TriplePub, TripleShare};
 
 /// The output of running the triple generation protocol.
 pub type TripleGenerationOutput<C> = (TripleShare<C>, TriplePub<C>);
 
 const LABEL: &[u8] = b"cait-sith v0.8.0 triple generation";
 
-async fn do_generation<C: CSCurve>(
+/// The most triples which can be generated by a single run of the protocol.
+const MAX_TRIPLE_COUNT: usize = 1 << 10;
+
+/// The polynomials, commitment opening, and proofs each participant reveals for a batch.
+type Reveal<C> = (
+    Vec<GroupPolynomial<C>>,
+    Vec<GroupPolynomial<C>>,
+    Vec<GroupPolynomial<C>>,
+    Randomizer,
+    Vec<dlog::Proof<C>>,
+    Vec<dlog::Proof<C>>,
+);
+
+/// The data used to fork the transcript, for a proof from a participant about a given triple.
+fn fork_data(p: Participant, i: usize) -> [u8; 12] {
+    let mut out = [0u8; 12];
+    out[..4].copy_from_slice(&p.bytes());
+    out[4..].copy_from_slice(&u64::try_from(i).unwrap().to_le_bytes());
+    out
+}
+
+async fn do_generation_many<C: CSCurve>(
     ctx: Context<'_>,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
-) -> Result<TripleGenerationOutput<C>, ProtocolError> {
-    let mut rng = OsRng;
+    count: usize,
+) -> Result<Vec<TripleGenerationOutput<C>>, ProtocolError> {
     let mut chan = ctx.shared_channel();
+    let mut rng = chan.rng(b"triple generation");
     let mut transcript = Transcript::new(LABEL);
 
     // Spec 1.1
+    transcript.message(b"session id", ctx.session_id());
     transcript.message(b"group", C::NAME);
     transcript.message(b"participants", &encode(&participants));
     // To allow interop between platforms where usize is different
@@ -40,19 +63,28 @@ async fn do_generation<C: CSCurve>(
         b"threshold",
         &u64::try_from(threshold).unwrap().to_be_bytes(),
     );
+    transcript.message(b"count", &u64::try_from(count).unwrap().to_be_bytes());
 
     // Spec 1.2
-    let e: Polynomial<C> = Polynomial::random(&mut rng, threshold);
-    let f: Polynomial<C> = Polynomial::random(&mut rng, threshold);
-    let mut l: Polynomial<C> = Polynomial::random(&mut rng, threshold);
+    let e: Vec<Polynomial<C>> = (0..count)
+        .map(|_| Polynomial::random(&mut rng, threshold))
+        .collect();
+    let f: Vec<Polynomial<C>> = (0..count)
+        .map(|_| Polynomial::random(&mut rng, threshold))
+        .collect();
+    let mut l: Vec<Polynomial<C>> = (0..count)
+        .map(|_| Polynomial::random(&mut rng, threshold))
+        .collect();
 
     // Spec 1.3
-    l.set_zero(C::Scalar::ZERO);
+    for l_k in &mut l {
+        l_k.set_zero(C::Scalar::ZERO);
+    }
 
     // Spec 1.4
-    let big_e_i = e.commit();
-    let big_f_i = f.commit();
-    let big_l_i = l.commit();
+    let big_e_i: Vec<_> = e.iter().map(|e_k| e_k.commit()).collect();
+    let big_f_i: Vec<_> = f.iter().map(|f_k| f_k.commit()).collect();
+    let big_l_i: Vec<_> = l.iter().map(|l_k| l_k.commit()).collect();
 
     // Spec 1.5
     let (my_commitment, my_randomizer) = commit(&mut rng, &(&big_e_i, &big_f_i, &big_l_i));
@@ -65,7 +97,7 @@ async fn do_generation<C: CSCurve>(
     let mut all_commitments = ParticipantMap::new(&participants);
     all_commitments.put(me, my_commitment);
     while !all_commitments.full() {
-        let (from, commitment) = chan.recv(wait0).await?;
+        let (from, commitment) = chan.recv(wait0, all_commitments.missing()).await?;
         all_commitments.put(from, commitment);
     }
 
@@ -78,9 +110,9 @@ async fn do_generation<C: CSCurve>(
     // Spec 2.4
     let fut = {
         let ctx = ctx.clone();
-        let e0 = e.evaluate_zero();
-        let f0 = f.evaluate_zero();
-        multiplication::<C>(ctx, my_confirmation, participants.clone(), me, e0, f0)
+        let e0 = e.iter().map(|e_k| e_k.evaluate_zero()).collect();
+        let f0 = f.iter().map(|f_k| f_k.evaluate_zero()).collect();
+        multiplication_many::<C>(ctx, my_confirmation, participants.clone(), me, e0, f0)
     };
     let multiplication_task = ctx.spawn(fut);
 
@@ -89,30 +121,34 @@ async fn do_generation<C: CSCurve>(
     chan.send_many(wait1, &my_confirmation).await;
 
     // Spec 2.6
-    let statement0 = dlog::Statement::<C> {
-        public: &big_e_i.evaluate_zero(),
-    };
-    let witness0 = dlog::Witness::<C> {
-        x: &e.evaluate_zero(),
-    };
-    let my_phi_proof0 = dlog::prove(
-        &mut rng,
-        &mut transcript.forked(b"dlog0", &me.bytes()),
-        statement0,
-        witness0,
-    );
-    let statement1 = dlog::Statement::<C> {
-        public: &big_f_i.evaluate_zero(),
-    };
-    let witness1 = dlog::Witness::<C> {
-        x: &f.evaluate_zero(),
-    };
-    let my_phi_proof1 = dlog::prove(
-        &mut rng,
-        &mut transcript.forked(b"dlog1", &me.bytes()),
-        statement1,
-        witness1,
-    );
+    let mut my_phi_proofs0 = Vec::with_capacity(count);
+    let mut my_phi_proofs1 = Vec::with_capacity(count);
+    for (k, (e_k, f_k)) in e.iter().zip(f.iter()).enumerate() {
+        let statement0 = dlog::Statement::<C> {
+            public: &big_e_i[k].evaluate_zero(),
+        };
+        let witness0 = dlog::Witness::<C> {
+            x: &e_k.evaluate_zero(),
+        };
+        my_phi_proofs0.push(dlog::prove(
+            &mut rng,
+            &mut transcript.forked(b"dlog0", &fork_data(me, k)),
+            statement0,
+            witness0,
+        ));
+        let statement1 = dlog::Statement::<C> {
+            public: &big_f_i[k].evaluate_zero(),
+        };
+        let witness1 = dlog::Witness::<C> {
+            x: &f_k.evaluate_zero(),
+        };
+        my_phi_proofs1.push(dlog::prove(
+            &mut rng,
+            &mut transcript.forked(b"dlog1", &fork_data(me, k)),
+            statement1,
+            witness1,
+        ));
+    }
 
     // Spec 2.7
     let wait2 = chan.next_waitpoint();
@@ -124,8 +160,8 @@ async fn do_generation<C: CSCurve>(
                 &big_f_i,
                 &big_l_i,
                 my_randomizer,
-                my_phi_proof0,
-                my_phi_proof1,
+                &my_phi_proofs0,
+                &my_phi_proofs1,
             ),
         )
         .await;
@@ -134,25 +170,43 @@ async fn do_generation<C: CSCurve>(
     // Spec 2.8
     let wait3 = chan.next_waitpoint();
     for p in participants.others(me) {
-        let a_i_j: ScalarPrimitive<C> = e.evaluate(&p.scalar::<C>()).into();
-        let b_i_j: ScalarPrimitive<C> = f.evaluate(&p.scalar::<C>()).into();
-        chan.send_private(wait3, p, &(a_i_j, b_i_j)).await;
+        let mut shares: Vec<(ScalarPrimitive<C>, ScalarPrimitive<C>)> = e
+            .iter()
+            .zip(f.iter())
+            .map(|(e_k, f_k)| {
+                (
+                    e_k.evaluate(&p.scalar::<C>()).into(),
+                    f_k.evaluate(&p.scalar::<C>()).into(),
+                )
+            })
+            .collect();
+        chan.send_private(wait3, p, &shares).await;
+        shares.zeroize();
     }
-    let mut a_i = e.evaluate(&me.scalar::<C>());
-    let mut b_i = f.evaluate(&me.scalar::<C>());
+    let mut a_i: Zeroizing<Vec<_>> = Zeroizing::new(
+        e.iter()
+            .map(|e_k| e_k.evaluate(&me.scalar::<C>()))
+            .collect(),
+    );
+    let mut b_i: Zeroizing<Vec<_>> = Zeroizing::new(
+        f.iter()
+            .map(|f_k| f_k.evaluate(&me.scalar::<C>()))
+            .collect(),
+    );
 
     // Spec 3.1 + 3.2
     let mut seen = ParticipantCounter::new(&participants);
     seen.put(me);
     while !seen.full() {
-        let (from, confirmation): (_, Digest) = chan.recv(wait1).await?;
+        let (from, confirmation): (_, Digest) = chan.recv(wait1, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
         if confirmation != my_confirmation {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "confirmation from {from:?} did not match expectation"
-            )));
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::InconsistentConfirmation,
+            });
         }
     }
 
@@ -160,6 +214,7 @@ async fn do_generation<C: CSCurve>(
     let mut big_e = big_e_i.clone();
     let mut big_f = big_f_i;
     let mut big_l = big_l_i;
+    let mut all_reveals = ParticipantMap::new(&participants);
     let mut big_e_j_zero = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
@@ -171,274 +226,425 @@ async fn do_generation<C: CSCurve>(
                 their_big_f,
                 their_big_l,
                 their_randomizer,
-                their_phi_proof0,
-                their_phi_proof1,
+                their_phi_proofs0,
+                their_phi_proofs1,
             ),
-        ): (
-            _,
-            (
-                GroupPolynomial<C>,
-                GroupPolynomial<C>,
-                GroupPolynomial<C>,
-                _,
-                _,
-                _,
-            ),
-        ) = chan.recv(wait2).await?;
+        ): (_, Reveal<C>) = chan.recv(wait2, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
 
-        if their_big_e.len() != threshold
-            || their_big_f.len() != threshold
-            || their_big_l.len() != threshold
+        if their_big_e.len() != count
+            || their_big_f.len() != count
+            || their_big_l.len() != count
+            || their_phi_proofs0.len() != count
+            || their_phi_proofs1.len() != count
         {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "polynomial from {from:?} has the wrong length"
-            )));
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::MalformedMessage,
+            });
         }
 
-        if !bool::from(their_big_l.evaluate_zero().is_identity()) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "L(0) from {from:?} is not 0"
-            )));
+        if their_big_e
+            .iter()
+            .chain(their_big_f.iter())
+            .chain(their_big_l.iter())
+            .any(|poly| poly.len() != threshold)
+        {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadPolynomial,
+            });
+        }
+
+        if their_big_l
+            .iter()
+            .any(|their_big_l_k| !bool::from(their_big_l_k.evaluate_zero().is_identity()))
+        {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadPolynomial,
+            });
         }
 
         if !all_commitments[from].check(
             &(&their_big_e, &their_big_f, &their_big_l),
             &their_randomizer,
         ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "commitment from {from:?} did not match revealed F"
-            )));
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::BadCommitmentOpening,
+            });
         }
 
-        let statement0 = dlog::Statement::<C> {
-            public: &their_big_e.evaluate_zero(),
-        };
-        if !dlog::verify(
-            &mut transcript.forked(b"dlog0", &from.bytes()),
-            statement0,
-            &their_phi_proof0,
-        ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+        for (k, (their_phi_proof0, their_phi_proof1)) in their_phi_proofs0
+            .iter()
+            .zip(their_phi_proofs1.iter())
+            .enumerate()
+        {
+            let statement0 = dlog::Statement::<C> {
+                public: &their_big_e[k].evaluate_zero(),
+            };
+            if !dlog::verify(
+                &mut transcript.forked(b"dlog0", &fork_data(from, k)),
+                statement0,
+                their_phi_proof0,
+            ) {
+                return Err(ProtocolError::Misbehaved {
+                    participant: from,
+                    reason: Misbehavior::BadDlogProof,
+                });
+            }
+
+            let statement1 = dlog::Statement::<C> {
+                public: &their_big_f[k].evaluate_zero(),
+            };
+            if !dlog::verify(
+                &mut transcript.forked(b"dlog1", &fork_data(from, k)),
+                statement1,
+                their_phi_proof1,
+            ) {
+                return Err(ProtocolError::Misbehaved {
+                    participant: from,
+                    reason: Misbehavior::BadDlogProof,
+                });
+            }
         }
 
-        let statement1 = dlog::Statement::<C> {
-            public: &their_big_f.evaluate_zero(),
-        };
-        if !dlog::verify(
-            &mut transcript.forked(b"dlog1", &from.bytes()),
-            statement1,
-            &their_phi_proof1,
-        ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+        big_e_j_zero.put(
+            from,
+            their_big_e
+                .iter()
+                .map(|their_big_e_k| their_big_e_k.evaluate_zero())
+                .collect::<Vec<_>>(),
+        );
+        for (big_e_k, their_big_e_k) in big_e.iter_mut().zip(their_big_e.iter()) {
+            *big_e_k += their_big_e_k;
         }
-
-        big_e_j_zero.put(from, their_big_e.evaluate_zero());
-        big_e += &their_big_e;
-        big_f += &their_big_f;
-        big_l += &their_big_l;
+        for (big_f_k, their_big_f_k) in big_f.iter_mut().zip(their_big_f.iter()) {
+            *big_f_k += their_big_f_k;
+        }
+        for (big_l_k, their_big_l_k) in big_l.iter_mut().zip(their_big_l.iter()) {
+            *big_l_k += their_big_l_k;
+        }
+        all_reveals.put(from, (their_big_e, their_big_f, their_big_l));
     }
 
     // Spec 3.5 + 3.6
+    let mut all_shares = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
-        let (from, (a_j_i, b_j_i)): (_, (ScalarPrimitive<C>, ScalarPrimitive<C>)) =
-            chan.recv(wait3).await?;
+        let (from, shares): (_, Vec<(ScalarPrimitive<C>, ScalarPrimitive<C>)>) =
+            chan.recv(wait3, seen.missing()).await?;
+        let shares = Zeroizing::new(shares);
         if !seen.put(from) {
             continue;
         }
-        a_i += &a_j_i.into();
-        b_i += &b_j_i.into();
+        if shares.len() != count {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::MalformedMessage,
+            });
+        }
+        for ((a_i_k, b_i_k), (a_j_i, b_j_i)) in
+            a_i.iter_mut().zip(b_i.iter_mut()).zip(shares.iter())
+        {
+            *a_i_k += &(*a_j_i).into();
+            *b_i_k += &(*b_j_i).into();
+        }
+        all_shares.put(from, shares);
     }
 
     // Spec 3.7
-    if big_e.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * a_i
-        || big_f.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * b_i
-    {
-        return Err(ProtocolError::AssertionFailed(
-            "received bad private share".to_string(),
-        ));
+    for k in 0..count {
+        if big_e[k].evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * a_i[k]
+            || big_f[k].evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * b_i[k]
+        {
+            // Only now do we check each share individually, to find out who sent a bad one.
+            for from in participants.others(me) {
+                let (their_big_e, their_big_f, _) = &all_reveals[from];
+                let (a_j_i, b_j_i) = all_shares[from][k];
+                if their_big_e[k].evaluate(&me.scalar::<C>())
+                    != C::ProjectivePoint::generator() * C::Scalar::from(a_j_i)
+                    || their_big_f[k].evaluate(&me.scalar::<C>())
+                        != C::ProjectivePoint::generator() * C::Scalar::from(b_j_i)
+                {
+                    return Err(ProtocolError::Misbehaved {
+                        participant: from,
+                        reason: Misbehavior::BadPrivateShare,
+                    });
+                }
+            }
+            return Err(ProtocolError::AssertionFailed(
+                "received bad private share".to_string(),
+            ));
+        }
     }
 
     // Spec 3.8
-    let big_c_i = big_f.evaluate_zero() * e.evaluate_zero();
+    let big_c_i: Vec<_> = big_f
+        .iter()
+        .zip(e.iter())
+        .map(|(big_f_k, e_k)| big_f_k.evaluate_zero() * e_k.evaluate_zero())
+        .collect();
 
     // Spec 3.9
-    let statement = dlogeq::Statement::<C> {
-        public0: &big_e_i.evaluate_zero(),
-        generator1: &big_f.evaluate_zero(),
-        public1: &big_c_i,
-    };
-    let witness = dlogeq::Witness {
-        x: &e.evaluate_zero(),
-    };
-    let my_phi_proof = dlogeq::prove(
-        &mut rng,
-        &mut transcript.forked(b"dlogeq0", &me.bytes()),
-        statement,
-        witness,
-    );
+    let mut my_phi_proofs = Vec::with_capacity(count);
+    for k in 0..count {
+        let statement = dlogeq::Statement::<C> {
+            public0: &big_e_i[k].evaluate_zero(),
+            generator1: &big_f[k].evaluate_zero(),
+            public1: &big_c_i[k],
+        };
+        let witness = dlogeq::Witness {
+            x: &e[k].evaluate_zero(),
+        };
+        let my_phi_proof = dlogeq::prove(
+            &mut rng,
+            &mut transcript.forked(b"dlogeq0", &fork_data(me, k)),
+            statement,
+            witness,
+        );
+        my_phi_proofs.push((
+            SerializablePoint::<C>::from_projective(&big_c_i[k]),
+            my_phi_proof,
+        ));
+    }
 
     // Spec 3.10
     let wait4 = chan.next_waitpoint();
-    chan.send_many(
-        wait4,
-        &(
-            SerializablePoint::<C>::from_projective(&big_c_i),
-            my_phi_proof,
-        ),
-    )
-    .await;
+    chan.send_many(wait4, &my_phi_proofs).await;
 
     // Spec 4.1 + 4.2 + 4.3
     seen.clear();
     seen.put(me);
     let mut big_c = big_c_i;
     while !seen.full() {
-        let (from, (big_c_j, their_phi_proof)): (_, (SerializablePoint<C>, _)) =
-            chan.recv(wait4).await?;
+        let (from, their_phi_proofs): (_, Vec<(SerializablePoint<C>, dlogeq::Proof<C>)>) =
+            chan.recv(wait4, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
-        let big_c_j = big_c_j.to_projective();
-
-        let statement = dlogeq::Statement::<C> {
-            public0: &big_e_j_zero[from],
-            generator1: &big_f.evaluate_zero(),
-            public1: &big_c_j,
-        };
-
-        if !dlogeq::verify(
-            &mut transcript.forked(b"dlogeq0", &from.bytes()),
-            statement,
-            &their_phi_proof,
-        ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlogeq proof from {from:?} failed to verify"
-            )));
+        if their_phi_proofs.len() != count {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::MalformedMessage,
+            });
         }
 
-        big_c += big_c_j;
+        for (k, (big_c_j, their_phi_proof)) in their_phi_proofs.into_iter().enumerate() {
+            let big_c_j = big_c_j.to_projective();
+
+            let statement = dlogeq::Statement::<C> {
+                public0: &big_e_j_zero[from][k],
+                generator1: &big_f[k].evaluate_zero(),
+                public1: &big_c_j,
+            };
+
+            if !dlogeq::verify(
+                &mut transcript.forked(b"dlogeq0", &fork_data(from, k)),
+                statement,
+                &their_phi_proof,
+            ) {
+                return Err(ProtocolError::Misbehaved {
+                    participant: from,
+                    reason: Misbehavior::BadDlogEqProof,
+                });
+            }
+
+            big_c[k] += big_c_j;
+        }
     }
 
     // Spec 4.4
-    let l0 = ctx.run(multiplication_task).await?;
+    let l0 = Zeroizing::new(ctx.run(multiplication_task).await?);
 
     // Spec 4.5
-    let hat_big_c_i = C::ProjectivePoint::generator() * l0;
+    let hat_big_c_i: Vec<_> = l0
+        .iter()
+        .map(|l0_k| C::ProjectivePoint::generator() * l0_k)
+        .collect();
 
     // Spec 4.6
-    let statement = dlog::Statement::<C> {
-        public: &hat_big_c_i,
-    };
-    let witness = dlog::Witness::<C> { x: &l0 };
-    let my_phi_proof = dlog::prove(
-        &mut rng,
-        &mut transcript.forked(b"dlog2", &me.bytes()),
-        statement,
-        witness,
-    );
+    let mut my_phi_proofs = Vec::with_capacity(count);
+    for (k, l0_k) in l0.iter().enumerate() {
+        let statement = dlog::Statement::<C> {
+            public: &hat_big_c_i[k],
+        };
+        let witness = dlog::Witness::<C> { x: l0_k };
+        let my_phi_proof = dlog::prove(
+            &mut rng,
+            &mut transcript.forked(b"dlog2", &fork_data(me, k)),
+            statement,
+            witness,
+        );
+        my_phi_proofs.push((
+            SerializablePoint::<C>::from_projective(&hat_big_c_i[k]),
+            my_phi_proof,
+        ));
+    }
 
     // Spec 4.8
     let wait5 = chan.next_waitpoint();
-    chan.send_many(
-        wait5,
-        &(
-            SerializablePoint::<C>::from_projective(&hat_big_c_i),
-            my_phi_proof,
-        ),
-    )
-    .await;
+    chan.send_many(wait5, &my_phi_proofs).await;
 
     // Spec 4.9
-    l.set_zero(l0);
+    for (l_k, l0_k) in l.iter_mut().zip(l0.iter()) {
+        l_k.set_zero(*l0_k);
+    }
     let wait6 = chan.next_waitpoint();
     for p in participants.others(me) {
-        let c_i_j: ScalarPrimitive<C> = l.evaluate(&p.scalar::<C>()).into();
+        let mut c_i_j: Vec<ScalarPrimitive<C>> = l
+            .iter()
+            .map(|l_k| l_k.evaluate(&p.scalar::<C>()).into())
+            .collect();
         chan.send_private(wait6, p, &c_i_j).await;
+        c_i_j.zeroize();
     }
-    let mut c_i = l.evaluate(&me.scalar::<C>());
+    let mut c_i: Zeroizing<Vec<_>> = Zeroizing::new(
+        l.iter()
+            .map(|l_k| l_k.evaluate(&me.scalar::<C>()))
+            .collect(),
+    );
 
     // Spec 5.1 + 5.2 + 5.3
+    let mut all_hat_big_c = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     let mut hat_big_c = hat_big_c_i;
     while !seen.full() {
-        let (from, (their_hat_big_c, their_phi_proof)): (_, (SerializablePoint<C>, _)) =
-            chan.recv(wait5).await?;
+        let (from, their_phi_proofs): (_, Vec<(SerializablePoint<C>, dlog::Proof<C>)>) =
+            chan.recv(wait5, seen.missing()).await?;
         if !seen.put(from) {
             continue;
         }
+        if their_phi_proofs.len() != count {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::MalformedMessage,
+            });
+        }
 
-        let their_hat_big_c = their_hat_big_c.to_projective();
-        let statement = dlog::Statement::<C> {
-            public: &their_hat_big_c,
-        };
-        if !dlog::verify(
-            &mut transcript.forked(b"dlog2", &from.bytes()),
-            statement,
-            &their_phi_proof,
-        ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+        let mut their_hat_big_c_v = Vec::with_capacity(count);
+        for (k, (their_hat_big_c, their_phi_proof)) in their_phi_proofs.into_iter().enumerate() {
+            let their_hat_big_c = their_hat_big_c.to_projective();
+            let statement = dlog::Statement::<C> {
+                public: &their_hat_big_c,
+            };
+            if !dlog::verify(
+                &mut transcript.forked(b"dlog2", &fork_data(from, k)),
+                statement,
+                &their_phi_proof,
+            ) {
+                return Err(ProtocolError::Misbehaved {
+                    participant: from,
+                    reason: Misbehavior::BadDlogProof,
+                });
+            }
+            hat_big_c[k] += &their_hat_big_c;
+            their_hat_big_c_v.push(their_hat_big_c);
         }
-        hat_big_c += &their_hat_big_c;
+        all_hat_big_c.put(from, their_hat_big_c_v);
     }
 
     // Spec 5.3
-    big_l.set_zero(hat_big_c);
+    for (big_l_k, hat_big_c_k) in big_l.iter_mut().zip(hat_big_c) {
+        big_l_k.set_zero(hat_big_c_k);
+    }
 
     // Spec 5.4
-    if big_l.evaluate_zero() != big_c {
+    if big_l
+        .iter()
+        .zip(big_c.iter())
+        .any(|(big_l_k, big_c_k)| big_l_k.evaluate_zero() != *big_c_k)
+    {
         return Err(ProtocolError::AssertionFailed(
             "final polynomial doesn't match C value".to_owned(),
         ));
     }
 
     // Spec 5.5 + 5.6
+    let mut all_c_j_i = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
-        let (from, c_j_i): (_, ScalarPrimitive<C>) = chan.recv(wait6).await?;
+        let (from, c_j_i): (_, Vec<ScalarPrimitive<C>>) = chan.recv(wait6, seen.missing()).await?;
+        let c_j_i = Zeroizing::new(c_j_i);
         if !seen.put(from) {
             continue;
         }
-        c_i += C::Scalar::from(c_j_i);
+        if c_j_i.len() != count {
+            return Err(ProtocolError::Misbehaved {
+                participant: from,
+                reason: Misbehavior::MalformedMessage,
+            });
+        }
+        for (c_i_k, c_j_i_k) in c_i.iter_mut().zip(c_j_i.iter()) {
+            *c_i_k += C::Scalar::from(*c_j_i_k);
+        }
+        all_c_j_i.put(from, c_j_i);
     }
 
     // Spec 5.7
-    if big_l.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * c_i {
-        return Err(ProtocolError::AssertionFailed(
-            "received bad private share of c".to_string(),
-        ));
+    for k in 0..count {
+        if big_l[k].evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * c_i[k] {
+            // Only now do we check each share individually, to find out who sent a bad one.
+            for from in participants.others(me) {
+                let (_, _, their_big_l) = &all_reveals[from];
+                // Their L(0) was 0, so replacing it with their Ĉ just adds Ĉ to the evaluation.
+                let expected = their_big_l[k].evaluate(&me.scalar::<C>()) + all_hat_big_c[from][k];
+                if expected != C::ProjectivePoint::generator() * C::Scalar::from(all_c_j_i[from][k])
+                {
+                    return Err(ProtocolError::Misbehaved {
+                        participant: from,
+                        reason: Misbehavior::BadPrivateShare,
+                    });
+                }
+            }
+            return Err(ProtocolError::AssertionFailed(
+                "received bad private share of c".to_string(),
+            ));
+        }
     }
 
-    let big_a = big_e.evaluate_zero().into();
-    let big_b = big_f.evaluate_zero().into();
-    let big_c = big_c.into();
-
-    Ok((
-        TripleShare {
-            a: a_i,
-            b: b_i,
-            c: c_i,
-        },
-        TriplePub {
-            big_a,
-            big_b,
-            big_c,
-            participants: participants.into(),
-            threshold,
-        },
-    ))
+    let participants: Vec<Participant> = participants.into();
+    let out = a_i
+        .iter()
+        .zip(b_i.iter())
+        .zip(c_i.iter())
+        .zip(big_e.iter().zip(big_f.iter()).zip(big_c))
+        .map(|(((a_i_k, b_i_k), c_i_k), ((big_e_k, big_f_k), big_c_k))| {
+            (
+                TripleShare {
+                    a: *a_i_k,
+                    b: *b_i_k,
+                    c: *c_i_k,
+                },
+                TriplePub {
+                    big_a: big_e_k.evaluate_zero().into(),
+                    big_b: big_f_k.evaluate_zero().into(),
+                    big_c: big_c_k.into(),
+                    participants: participants.clone(),
+                    threshold,
+                },
+            )
+        })
+        .collect();
+
+    Ok(out)
+}
+
+async fn do_generation<C: CSCurve>(
+    ctx: Context<'_>,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+) -> Result<TripleGenerationOutput<C>, ProtocolError> {
+    let mut out = do_generation_many::<C>(ctx, participants, me, threshold, 1).await?;
+    // Unwrapping is fine, since we asked for exactly one triple.
+    Ok(out.pop().unwrap())
 }
 
 /// Generate a triple through a multi-party protocol.
@@ -448,10 +654,14 @@ async fn do_generation<C: CSCurve>(
 ///
 /// The resulting triple will be threshold shared, according to the threshold
 /// provided to this function.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
 pub fn generate_triple<C: CSCurve>(
     participants: &[Participant],
     me: Participant,
     threshold: usize,
+    session_id: &[u8],
 ) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
@@ -470,22 +680,71 @@ pub fn generate_triple<C: CSCurve>(
         InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
     })?;
 
-    let ctx = Context::new();
+    let ctx = Context::new(ProtocolId::TripleGeneration, session_id);
     let fut = do_generation(ctx.clone(), participants, me, threshold);
     Ok(make_protocol(ctx, fut))
 }
 
+/// Generate many triples at once, through a multi-party protocol.
+///
+/// This is like [generate_triple], except that `count` triples are produced
+/// by a single run of the protocol, which amortizes the cost of setting up
+/// the oblivious transfers, as well as the number of rounds, across the batch.
+/// At most 1024 triples can be generated at once.
+///
+/// The `session_id` must be agreed upon by all participants, and must be unique
+/// to this run of the protocol.
+pub fn generate_triple_many<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    count: usize,
+    session_id: &[u8],
+) -> Result<impl Protocol<Output = Vec<TripleGenerationOutput<C>>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    // Spec 1.1
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+    if count == 0 {
+        return Err(InitializationError::BadParameters(
+            "triple count must be > 0".to_string(),
+        ));
+    }
+    if count > MAX_TRIPLE_COUNT {
+        return Err(InitializationError::BadParameters(format!(
+            "triple count must be <= {}",
+            MAX_TRIPLE_COUNT
+        )));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    let ctx = Context::new(ProtocolId::TripleGenerationMany, session_id);
+    let fut = do_generation_many(ctx.clone(), participants, me, threshold, count);
+    Ok(make_protocol(ctx, fut))
+}
+
 #[cfg(test)]
 mod test {
     use k256::{ProjectivePoint, Secp256k1};
 
     use crate::{
         participants::ParticipantList,
-        protocol::{run_protocol, Participant, Protocol, ProtocolError},
-        triples::generate_triple,
+        protocol::{run_protocol, InitializationError, Participant, Protocol, ProtocolError},
+        triples::{generate_triple, generate_triple_many},
     };
 
-    use super::TripleGenerationOutput;
+    use super::{TripleGenerationOutput, MAX_TRIPLE_COUNT};
 
     #[test]
     fn test_triple_generation() -> Result<(), ProtocolError> {
@@ -503,7 +762,7 @@ mod test {
         )> = Vec::with_capacity(participants.len());
 
         for &p in &participants {
-            let protocol = generate_triple(&participants, p, threshold);
+            let protocol = generate_triple(&participants, p, threshold, b"test triple");
             assert!(protocol.is_ok());
             let protocol = protocol.unwrap();
             protocols.push((p, Box::new(protocol)));
@@ -518,7 +777,7 @@ mod test {
         let triple_pub = result[2].1 .1.clone();
 
         let participants = vec![result[0].0, result[1].0, result[2].0];
-        let triple_shares = vec![
+        let triple_shares = [
             result[0].1 .0.clone(),
             result[1].1 .0.clone(),
             result[2].1 .0.clone(),
@@ -544,4 +803,74 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_triple_generation_many() -> Result<(), ProtocolError> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let count = 3;
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<TripleGenerationOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+
+        for &p in &participants {
+            let protocol =
+                generate_triple_many(&participants, p, threshold, count, b"test triple many");
+            assert!(protocol.is_ok());
+            let protocol = protocol.unwrap();
+            protocols.push((p, Box::new(protocol)));
+        }
+
+        let result = run_protocol(protocols)?;
+
+        assert!(result.len() == participants.len());
+        for k in 0..count {
+            assert_eq!(result[0].1.len(), count);
+            assert_eq!(result[0].1[k].1, result[1].1[k].1);
+            assert_eq!(result[1].1[k].1, result[2].1[k].1);
+
+            let triple_pub = &result[0].1[k].1;
+            // Any two participants should be able to reconstruct the triple.
+            let (p0, p1) = (result[0].0, result[1].0);
+            let subset = ParticipantList::new(&[p0, p1]).unwrap();
+            let (share0, share1) = (&result[0].1[k].0, &result[1].1[k].0);
+            let a = subset.lagrange::<Secp256k1>(p0) * share0.a
+                + subset.lagrange::<Secp256k1>(p1) * share1.a;
+            let b = subset.lagrange::<Secp256k1>(p0) * share0.b
+                + subset.lagrange::<Secp256k1>(p1) * share1.b;
+            let c = subset.lagrange::<Secp256k1>(p0) * share0.c
+                + subset.lagrange::<Secp256k1>(p1) * share1.c;
+            assert_eq!(ProjectivePoint::GENERATOR * a, triple_pub.big_a);
+            assert_eq!(ProjectivePoint::GENERATOR * b, triple_pub.big_b);
+            assert_eq!(ProjectivePoint::GENERATOR * c, triple_pub.big_c);
+            assert_eq!(a * b, c);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_triple_generation_many_checks_count() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        for count in [0, MAX_TRIPLE_COUNT + 1] {
+            let protocol = generate_triple_many::<Secp256k1>(
+                &participants,
+                participants[0],
+                2,
+                count,
+                b"test triple many",
+            );
+            assert!(matches!(
+                protocol,
+                Err(InitializationError::BadParameters(_))
+            ));
+        }
+    }
 }
@@ -20,26 +20,47 @@
 //! The other way is to run a protocol generating a secret shared triple without any party
 //! learning the secret values. This is better because no party learns the value of the
 //! triple, which needs to be kept secret. This method is supported by the [generate_triple]
-//! protocol.
+//! protocol, and [generate_triple_many] can be used to generate a batch of triples at once,
+//! which is cheaper per triple.
 //!
 //! This protocol requires a setup protocol to be one once beforehand.
 //! After this setup protocol has been run, an arbitarary number of triples can
 //! be generated.
 use elliptic_curve::{Field, Group};
 use rand_core::CryptoRngCore;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::{compat::CSCurve, math::Polynomial, protocol::Participant};
+use crate::{
+    compat::CSCurve,
+    math::Polynomial,
+    protocol::Participant,
+    serde::{
+        deserialize_affine_point, deserialize_scalar, serialize_affine_point, serialize_scalar,
+    },
+};
 
 /// Represents the public part of a triple.
 ///
 /// This contains commitments to each part of the triple.
 ///
 /// We also record who participated in the protocol,
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TriplePub<C: CSCurve> {
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
     pub big_a: C::AffinePoint,
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
     pub big_b: C::AffinePoint,
+    #[serde(
+        serialize_with = "serialize_affine_point::<C, _>",
+        deserialize_with = "deserialize_affine_point::<C, _>"
+    )]
     pub big_c: C::AffinePoint,
     /// The participants in generating this triple.
     pub participants: Vec<Participant>,
@@ -52,10 +73,24 @@ pub struct TriplePub<C: CSCurve> {
 /// This consists of shares of each individual part.
 ///
 /// i.e. we have a share of a, b, and c such that a * b = c.
-#[derive(Clone, Debug)]
+///
+/// These shares are wiped from memory when this is dropped.
+#[derive(Clone, Debug, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct TripleShare<C: CSCurve> {
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub a: C::Scalar,
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub b: C::Scalar,
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
     pub c: C::Scalar,
 }
 
@@ -108,4 +143,4 @@ mod mta;
 mod multiplication;
 mod random_ot_extension;
 
-pub use generation::{generate_triple, TripleGenerationOutput};
+pub use generation::{generate_triple, generate_triple_many, TripleGenerationOutput};
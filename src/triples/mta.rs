@@ -1,13 +1,13 @@
 use elliptic_curve::{Field, ScalarPrimitive};
 use magikitten::MeowRng;
-use rand_core::{OsRng, RngCore};
+use rand_core::RngCore;
 use subtle::{Choice, ConditionallySelectable};
 
 use crate::{
     compat::CSCurve,
     protocol::{
         internal::{make_protocol, Context, PrivateChannel},
-        run_two_party_protocol, Participant, ProtocolError,
+        run_two_party_protocol, Participant, ProtocolError, ProtocolId,
     },
 };
 
@@ -20,7 +20,8 @@ pub async fn mta_sender<C: CSCurve>(
     let size = v.len();
 
     // Step 1
-    let delta: Vec<_> = (0..size).map(|_| C::Scalar::random(&mut OsRng)).collect();
+    let mut rng = chan.rng(b"mta delta");
+    let delta: Vec<_> = (0..size).map(|_| C::Scalar::random(&mut rng)).collect();
 
     // Step 2
     let c: Vec<(ScalarPrimitive<C>, ScalarPrimitive<C>)> = delta
@@ -68,7 +69,7 @@ pub async fn mta_receiver<C: CSCurve>(
 
     // Step 4
     let mut seed = [0u8; 32];
-    OsRng.fill_bytes(&mut seed);
+    chan.rng(b"mta seed").fill_bytes(&mut seed);
     let mut prng = MeowRng::new(&seed);
     let chi: Vec<C::Scalar> = (1..size).map(|_| C::Scalar::random(&mut prng)).collect();
 
@@ -102,8 +103,8 @@ fn run_mta<C: CSCurve>(
 ) -> Result<(C::Scalar, C::Scalar), ProtocolError> {
     let s = Participant::from(0u32);
     let r = Participant::from(1u32);
-    let ctx_s = Context::new();
-    let ctx_r = Context::new();
+    let ctx_s = Context::new(ProtocolId::TripleGeneration, &[]);
+    let ctx_r = Context::new(ProtocolId::TripleGeneration, &[]);
 
     run_two_party_protocol(
         s,
@@ -123,7 +124,7 @@ fn run_mta<C: CSCurve>(
 mod test {
     use ecdsa::elliptic_curve::{bigint::Bounded, Curve};
     use k256::{Scalar, Secp256k1};
-    use rand_core::RngCore;
+    use rand_core::OsRng;
 
     use crate::constants::SECURITY_PARAMETER;
 
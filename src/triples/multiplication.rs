@@ -1,3 +1,5 @@
+use zeroize::Zeroizing;
+
 use crate::{
     compat::CSCurve,
     constants::SECURITY_PARAMETER,
@@ -21,85 +23,115 @@ pub async fn multiplication_sender<'a, C: CSCurve>(
     ctx: Context<'a>,
     chan: PrivateChannel,
     sid: &[u8],
-    a_i: &C::Scalar,
-    b_i: &C::Scalar,
-) -> Result<C::Scalar, ProtocolError> {
+    a_i: &[C::Scalar],
+    b_i: &[C::Scalar],
+) -> Result<Vec<C::Scalar>, ProtocolError> {
     // First, run a fresh batch random OT ourselves
     let (delta, k) = batch_random_ot_receiver::<C>(ctx.clone(), chan.child(0)).await?;
 
     let batch_size = C::BITS + SECURITY_PARAMETER;
     // Step 1
-    let mut res0 = random_ot_extension_sender::<C>(
+    let res = random_ot_extension_sender::<C>(
         chan.child(1),
         RandomOtExtensionParams {
             sid,
-            batch_size: 2 * batch_size,
+            batch_size: 2 * batch_size * a_i.len(),
         },
         delta,
         &k,
     )
     .await?;
-    let res1 = res0.split_off(batch_size);
 
     // Step 2
-    let task0 = ctx.spawn(mta_sender::<C>(chan.child(2), res0, *a_i));
-    let task1 = ctx.spawn(mta_sender::<C>(chan.child(3), res1, *b_i));
+    let mut chunks = res.chunks_exact(batch_size);
+    let mut tasks = Vec::with_capacity(a_i.len());
+    for (j, (a_i_j, b_i_j)) in (0u64..).zip(a_i.iter().zip(b_i.iter())) {
+        // Unwrapping is fine, since we extended exactly two batches for each multiplication.
+        let res0 = chunks.next().unwrap().to_vec();
+        let res1 = chunks.next().unwrap().to_vec();
+        let task0 = ctx.spawn(mta_sender::<C>(chan.child(2 + 2 * j), res0, *a_i_j));
+        let task1 = ctx.spawn(mta_sender::<C>(chan.child(3 + 2 * j), res1, *b_i_j));
+        tasks.push((task0, task1));
+    }
 
     // Step 3
-    let gamma0 = ctx.run(task0).await?;
-    let gamma1 = ctx.run(task1).await?;
+    let mut out = Vec::with_capacity(tasks.len());
+    for (task0, task1) in tasks {
+        let gamma0 = ctx.run(task0).await?;
+        let gamma1 = ctx.run(task1).await?;
+        out.push(gamma0 + gamma1);
+    }
 
-    Ok(gamma0 + gamma1)
+    Ok(out)
 }
 
 pub async fn multiplication_receiver<'a, C: CSCurve>(
     ctx: Context<'a>,
     chan: PrivateChannel,
     sid: &[u8],
-    a_i: &C::Scalar,
-    b_i: &C::Scalar,
-) -> Result<C::Scalar, ProtocolError> {
+    a_i: &[C::Scalar],
+    b_i: &[C::Scalar],
+) -> Result<Vec<C::Scalar>, ProtocolError> {
     // First, run a fresh batch random OT ourselves
     let (k0, k1) = batch_random_ot_sender::<C>(ctx.clone(), chan.child(0)).await?;
 
     let batch_size = C::BITS + SECURITY_PARAMETER;
     // Step 1
-    let mut res0 = random_ot_extension_receiver::<C>(
+    let res = random_ot_extension_receiver::<C>(
         chan.child(1),
         RandomOtExtensionParams {
             sid,
-            batch_size: 2 * batch_size,
+            batch_size: 2 * batch_size * a_i.len(),
         },
         &k0,
         &k1,
     )
     .await?;
-    let res1 = res0.split_off(batch_size);
 
     // Step 2
-    let task0 = ctx.spawn(mta_receiver::<C>(chan.child(2), res0, *b_i));
-    let task1 = ctx.spawn(mta_receiver::<C>(chan.child(3), res1, *a_i));
+    let mut chunks = res.chunks_exact(batch_size);
+    let mut tasks = Vec::with_capacity(a_i.len());
+    for (j, (a_i_j, b_i_j)) in (0u64..).zip(a_i.iter().zip(b_i.iter())) {
+        // Unwrapping is fine, since we extended exactly two batches for each multiplication.
+        let res0 = chunks.next().unwrap().to_vec();
+        let res1 = chunks.next().unwrap().to_vec();
+        let task0 = ctx.spawn(mta_receiver::<C>(chan.child(2 + 2 * j), res0, *b_i_j));
+        let task1 = ctx.spawn(mta_receiver::<C>(chan.child(3 + 2 * j), res1, *a_i_j));
+        tasks.push((task0, task1));
+    }
 
     // Step 3
-    let gamma0 = ctx.run(task0).await?;
-    let gamma1 = ctx.run(task1).await?;
+    let mut out = Vec::with_capacity(tasks.len());
+    for (task0, task1) in tasks {
+        let gamma0 = ctx.run(task0).await?;
+        let gamma1 = ctx.run(task1).await?;
+        out.push(gamma0 + gamma1);
+    }
 
-    Ok(gamma0 + gamma1)
+    Ok(out)
 }
 
-pub async fn multiplication<C: CSCurve>(
+/// Run many multiplications at once.
+///
+/// Each party holds a share of several pairs `(a, b)`, and receives a share of each product,
+/// in the same order. The OTs for all of these products are set up together with each other party.
+pub async fn multiplication_many<C: CSCurve>(
     ctx: Context<'_>,
     sid: Digest,
     participants: ParticipantList,
     me: Participant,
-    a_i: C::Scalar,
-    b_i: C::Scalar,
-) -> Result<C::Scalar, ProtocolError> {
+    a_i: Vec<C::Scalar>,
+    b_i: Vec<C::Scalar>,
+) -> Result<Vec<C::Scalar>, ProtocolError> {
+    let a_i = Zeroizing::new(a_i);
+    let b_i = Zeroizing::new(b_i);
     let mut tasks = Vec::with_capacity(participants.len() - 1);
     for p in participants.others(me) {
         let fut = {
             let ctx = ctx.clone();
             let chan = ctx.private_channel(me, p);
+            let a_i = a_i.clone();
+            let b_i = b_i.clone();
             async move {
                 if p < me {
                     multiplication_sender::<C>(ctx, chan, sid.as_ref(), &a_i, &b_i).await
@@ -110,9 +142,11 @@ pub async fn multiplication<C: CSCurve>(
         };
         tasks.push(ctx.spawn(fut));
     }
-    let mut out = a_i * b_i;
+    let mut out: Vec<C::Scalar> = a_i.iter().zip(b_i.iter()).map(|(a, b)| *a * b).collect();
     for task in tasks {
-        out += task.await?;
+        for (out_j, gamma_j) in out.iter_mut().zip(task.await?) {
+            *out_j += gamma_j;
+        }
     }
     Ok(out)
 }
@@ -127,11 +161,11 @@ mod test {
         participants::ParticipantList,
         protocol::{
             internal::{make_protocol, Context},
-            run_protocol, Participant, Protocol, ProtocolError,
+            run_protocol, Participant, Protocol, ProtocolError, ProtocolId,
         },
     };
 
-    use super::multiplication;
+    use super::multiplication_many;
 
     #[test]
     fn test_multiplication() -> Result<(), ProtocolError> {
@@ -141,31 +175,44 @@ mod test {
             Participant::from(2u32),
         ];
 
+        let count = 3;
+
         let prep: Vec<_> = participants
             .iter()
             .map(|p| {
-                let a_i = Scalar::generate_biased(&mut OsRng);
-                let b_i = Scalar::generate_biased(&mut OsRng);
+                let a_i: Vec<_> = (0..count)
+                    .map(|_| Scalar::generate_biased(&mut OsRng))
+                    .collect();
+                let b_i: Vec<_> = (0..count)
+                    .map(|_| Scalar::generate_biased(&mut OsRng))
+                    .collect();
                 (p, a_i, b_i)
             })
             .collect();
-        let a = prep
-            .iter()
-            .fold(Scalar::ZERO, |acc, (_, a_i, _)| acc + a_i);
-        let b = prep
-            .iter()
-            .fold(Scalar::ZERO, |acc, (_, _, b_i)| acc + b_i);
+        let a: Vec<_> = (0..count)
+            .map(|j| {
+                prep.iter()
+                    .fold(Scalar::ZERO, |acc, (_, a_i, _)| acc + a_i[j])
+            })
+            .collect();
+        let b: Vec<_> = (0..count)
+            .map(|j| {
+                prep.iter()
+                    .fold(Scalar::ZERO, |acc, (_, _, b_i)| acc + b_i[j])
+            })
+            .collect();
 
-        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> =
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Vec<Scalar>>>)> =
             Vec::with_capacity(prep.len());
 
         let sid = hash(b"sid");
 
         for (p, a_i, b_i) in prep {
-            let ctx = Context::new();
+            let ctx = Context::new(ProtocolId::TripleGeneration, sid.as_ref());
             let prot = make_protocol(
                 ctx.clone(),
-                multiplication::<Secp256k1>(
+                multiplication_many::<Secp256k1>(
                     ctx,
                     sid,
                     ParticipantList::new(&participants).unwrap(),
@@ -178,11 +225,12 @@ mod test {
         }
 
         let result = run_protocol(protocols)?;
-        let c = result
-            .into_iter()
-            .fold(Scalar::ZERO, |acc, (_, c_i)| acc + c_i);
-
-        assert_eq!(a * b, c);
+        for j in 0..count {
+            let c = result
+                .iter()
+                .fold(Scalar::ZERO, |acc, (_, c_i)| acc + c_i[j]);
+            assert_eq!(a[j] * b[j], c);
+        }
 
         Ok(())
     }
@@ -1,7 +1,7 @@
 use ck_meow::Meow;
 use elliptic_curve::{CurveArithmetic};
 use magikitten::MeowRng;
-use rand_core::{OsRng, RngCore};
+use rand_core::RngCore;
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
 use crate::{
@@ -9,7 +9,7 @@ use crate::{
     constants::SECURITY_PARAMETER,
     protocol::{
         internal::{make_protocol, Context, PrivateChannel},
-        run_two_party_protocol, Participant, ProtocolError,
+        run_two_party_protocol, Participant, ProtocolError, ProtocolId,
     },
 };
 
@@ -80,7 +80,7 @@ pub async fn random_ot_extension_sender<C: CSCurve>(
 
     // Step 5
     let mut seed = [0u8; 32];
-    OsRng.fill_bytes(&mut seed);
+    chan.rng(b"random ot extension seed").fill_bytes(&mut seed);
     let wait0 = chan.next_waitpoint();
     chan.send(wait0, &seed).await;
 
@@ -137,7 +137,7 @@ pub async fn random_ot_extension_receiver<C: CSCurve>(
     let adjusted_size = adjust_size(params.batch_size);
 
     // Step 1
-    let b = ChoiceVector::random(&mut OsRng, adjusted_size);
+    let b = ChoiceVector::random(&mut chan.rng(b"random ot extension b"), adjusted_size);
     let x: BitMatrix = b
         .bits()
         .map(|b_i| BitVector::conditional_select(&BitVector::zero(), &!BitVector::zero(), b_i))
@@ -214,8 +214,8 @@ fn run_random_ot<C: CSCurve>(
 > {
     let s = Participant::from(0u32);
     let r = Participant::from(1u32);
-    let ctx_s = Context::new();
-    let ctx_r = Context::new();
+    let ctx_s = Context::new(ProtocolId::TripleGeneration, &[]);
+    let ctx_r = Context::new(ProtocolId::TripleGeneration, &[]);
 
     let params = RandomOtExtensionParams { sid, batch_size };
 